@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How many nodes a `Meter` counts between looks at the clock and the cancel
+/// flag.
+const CHECK_EVERY: u64 = 1024;
+
+/// Why a search gave up before reaching an answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Exhausted {
+    Deadline,
+    Nodes,
+    Cancelled,
+}
+
+impl Exhausted {
+    pub fn name(self) -> &'static str {
+        return match self {
+            Exhausted::Deadline => "deadline passed",
+            Exhausted::Nodes => "node limit reached",
+            Exhausted::Cancelled => "cancelled",
+        };
+    }
+}
+
+/// Result of a search run under a `Budget`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome<T> {
+    Solved(T),
+    Infeasible,
+    Unknown(Exhausted),
+}
+
+impl<T> Outcome<T> {
+    pub fn is_known(&self) -> bool {
+        return !matches!(self, Outcome::Unknown(_));
+    }
+
+    pub fn solved(&self) -> Option<&T> {
+        return match self {
+            Outcome::Solved(x) => Some(x),
+            _ => None,
+        };
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Outcome<U> {
+        return match self {
+            Outcome::Solved(x) => Outcome::Solved(f(x)),
+            Outcome::Infeasible => Outcome::Infeasible,
+            Outcome::Unknown(reason) => Outcome::Unknown(reason),
+        };
+    }
+}
+
+/// Limits on a single search: wall-clock time, nodes visited and a flag
+/// another thread can raise to stop it. The default has no limits.
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    timeout: Option<Duration>,
+    node_limit: Option<u64>,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+fn parse_env<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    return Some(
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("Could not parse {name} `{value}`")),
+    );
+}
+
+impl Budget {
+    /// Reads the per-search limits from `SEARCH_TIMEOUT`, in seconds, and
+    /// `SEARCH_NODES`. Unset variables leave that limit off.
+    pub fn from_env() -> Budget {
+        let budget = Budget {
+            timeout: parse_env::<f64>("SEARCH_TIMEOUT").map(Duration::from_secs_f64),
+            ..Budget::default()
+        };
+        return match parse_env("SEARCH_NODES") {
+            Some(limit) => budget.with_node_limit(limit),
+            None => budget,
+        };
+    }
+
+    /// Stops every search started from this budget after `limit` nodes.
+    pub fn with_node_limit(self, limit: u64) -> Budget {
+        return Budget {
+            node_limit: Some(limit),
+            ..self
+        };
+    }
+
+    /// Stops every search started from this budget once `flag` is set.
+    pub fn with_cancel(self, flag: Arc<AtomicBool>) -> Budget {
+        return Budget {
+            cancel: Some(flag),
+            ..self
+        };
+    }
+
+    /// Starts the clock for one search.
+    pub fn start(&self) -> Meter {
+        return Meter {
+            deadline: self.timeout.map(|t| Instant::now() + t),
+            node_limit: self.node_limit,
+            nodes: 0,
+            cancel: self.cancel.clone(),
+            exhausted: None,
+        };
+    }
+}
+
+/// Tracks one search against its `Budget`.
+#[derive(Debug)]
+pub struct Meter {
+    deadline: Option<Instant>,
+    node_limit: Option<u64>,
+    nodes: u64,
+    cancel: Option<Arc<AtomicBool>>,
+    exhausted: Option<Exhausted>,
+}
+
+impl Meter {
+    /// Counts a node. Returns false once the budget is spent, and keeps
+    /// returning false after that.
+    pub fn tick(&mut self) -> bool {
+        if self.exhausted.is_some() {
+            return false;
+        }
+        self.nodes += 1;
+        if self.node_limit.is_some_and(|limit| self.nodes > limit) {
+            self.exhausted = Some(Exhausted::Nodes);
+        } else if self.nodes.is_multiple_of(CHECK_EVERY) {
+            if self.deadline.is_some_and(|d| Instant::now() >= d) {
+                self.exhausted = Some(Exhausted::Deadline);
+            } else if self
+                .cancel
+                .as_ref()
+                .is_some_and(|c| c.load(Ordering::Relaxed))
+            {
+                self.exhausted = Some(Exhausted::Cancelled);
+            }
+        }
+        return self.exhausted.is_none();
+    }
+
+    /// Turns what a search found into an `Outcome`. Finding nothing only
+    /// proves infeasibility if the search was never cut short.
+    pub fn outcome<T>(&self, found: Option<T>) -> Outcome<T> {
+        if let Some(x) = found {
+            return Outcome::Solved(x);
+        }
+        return match self.exhausted {
+            Some(reason) => Outcome::Unknown(reason),
+            None => Outcome::Infeasible,
+        };
+    }
+}
@@ -1,3 +1,6 @@
+use crate::logging::debug;
+use crate::output;
+
 fn dir_sign(dir: char) -> i32 {
     let ldir = dir.to_ascii_uppercase();
     match ldir {
@@ -55,11 +58,11 @@ fn part2_iter(start: i32, inc: i32) -> (i32, i32) {
 fn part2(histories: &Vec<i32>) -> i32 {
     let mut tracker = 50;
     let mut zeros = 0;
-    for (_j, x) in histories.iter().enumerate() {
+    for (j, x) in histories.iter().enumerate() {
         let (t_j, z_j) = part2_iter(tracker, *x);
         tracker = t_j;
         zeros += z_j;
-        // println!("{j}\t{tracker}\t{zeros}\t\t{x}");
+        debug!("rotation {j} by {x}: dial at {tracker}, {zeros} zeros so far");
     }
     return zeros;
 }
@@ -67,7 +70,7 @@ fn part2(histories: &Vec<i32>) -> i32 {
 pub fn day1(contents: &String) {
     let histories: Vec<i32> = contents.lines().map(get_number).collect();
     let p1 = part1(&histories);
-    println!("Part 1: {p1}");
+    output::answer(1, p1);
     let p2 = part2(&histories);
-    println!("Part 2: {p2}");
+    output::answer(2, p2);
 }
@@ -1,6 +1,12 @@
+use crate::budget::{self, Budget, Exhausted};
+use crate::gf2::min_weight_solution;
+use crate::ilp::min_sum_solution;
+use crate::output;
+use crate::progress::ReportProgress;
+#[cfg(feature = "lpsolve")]
 use good_lp::{default_solver, variable, Expression, ProblemVariables, Solution, SolverModel};
-use indicatif::ProgressIterator;
-use std::cmp::min;
+use ndarray::prelude::*;
+use std::str::FromStr;
 
 #[derive(Debug)]
 struct Machine {
@@ -9,76 +15,128 @@ struct Machine {
     joltage: Vec<u16>,
 }
 
-fn make_machine(line: &str) -> Machine {
+#[derive(Debug, Clone, PartialEq)]
+enum MachineError {
+    Parse(String),
+    ButtonOutOfRange { button: usize, index: usize },
+}
+
+/// Result of solving one machine.
+#[derive(Debug, Clone, PartialEq)]
+enum Outcome {
+    Solved(u64),
+    Infeasible,
+    Unknown(Exhausted),
+    Invalid(MachineError),
+}
+
+/// Strips the `open` and `close` delimiters from `token`.
+fn unwrap_block(token: &str, open: char, close: char) -> Result<&str, MachineError> {
+    return token
+        .strip_prefix(open)
+        .and_then(|t| t.strip_suffix(close))
+        .ok_or_else(|| MachineError::Parse(format!("expected {open}...{close}, found `{token}`")));
+}
+
+fn parse_list<T: FromStr>(block: &str) -> Result<Vec<T>, MachineError> {
+    return block
+        .split(',')
+        .map(|x| {
+            x.parse::<T>()
+                .map_err(|_| MachineError::Parse(format!("invalid number `{x}`")))
+        })
+        .collect();
+}
+
+fn make_machine(line: &str) -> Result<Machine, MachineError> {
     let mut sp = line.split_whitespace();
-    let light_str = sp.next().unwrap();
-    let lights: Vec<_> = light_str[1..(light_str.len() - 1)]
+    let light_str = sp
+        .next()
+        .ok_or_else(|| MachineError::Parse(String::from("empty line")))?;
+    let lights: Vec<_> = unwrap_block(light_str, '[', ']')?
         .chars()
-        .map(|x| x == '#')
-        .collect();
+        .map(|x| match x {
+            '#' => Ok(true),
+            '.' => Ok(false),
+            _ => Err(MachineError::Parse(format!("invalid light `{x}`"))),
+        })
+        .collect::<Result<_, _>>()?;
     let mut buttons = Vec::<Vec<usize>>::new();
     let joltage: Vec<u16>;
     loop {
-        let next_button = sp.next().unwrap();
+        let next_button = sp
+            .next()
+            .ok_or_else(|| MachineError::Parse(String::from("missing {...} joltage block")))?;
         if next_button.starts_with('{') {
-            joltage = next_button[1..(next_button.len() - 1)]
-                .split(',')
-                .map(|x| x.parse::<u16>().unwrap())
-                .collect();
+            joltage = parse_list(unwrap_block(next_button, '{', '}')?)?;
             break;
         }
-        buttons.push(
-            next_button[1..(next_button.len() - 1)]
-                .split(',')
-                .map(|x| x.parse::<usize>().unwrap())
-                .collect(),
-        )
-    }
-    return Machine {
+        buttons.push(parse_list(unwrap_block(next_button, '(', ')')?)?)
+    }
+    if let Some(extra) = sp.next() {
+        return Err(MachineError::Parse(format!(
+            "unexpected `{extra}` after joltage"
+        )));
+    }
+    if joltage.len() != lights.len() {
+        let msg = format!("{} lights but {} joltages", lights.len(), joltage.len());
+        return Err(MachineError::Parse(msg));
+    }
+    for (b_idx, button) in buttons.iter().enumerate() {
+        if let Some(&index) = button.iter().find(|&&idx| idx >= lights.len()) {
+            return Err(MachineError::ButtonOutOfRange {
+                button: b_idx,
+                index,
+            });
+        }
+    }
+    return Ok(Machine {
         lights,
         buttons,
         joltage,
-    };
+    });
 }
 
-fn part1_helper(
-    light_target: &Vec<bool>,
-    light_curr: &Vec<bool>,
-    buttons: &[Vec<usize>],
-    buttons_pressed: u16,
-) -> u16 {
-    if light_target == light_curr {
-        return buttons_pressed;
-    }
-    if buttons.len() == 0 {
-        return u16::MAX;
-    }
-    let button = &buttons[0];
-    let buttons_left = &buttons[1..];
-    let not_press_button = part1_helper(light_target, light_curr, buttons_left, buttons_pressed);
-    let mut light_pressed = light_curr.clone();
-    for &b_idx in button {
-        light_pressed[b_idx] = !light_pressed[b_idx];
-    }
-    let press_button = part1_helper(
-        light_target,
-        &light_pressed,
-        buttons_left,
-        buttons_pressed + 1,
-    );
-    return min(not_press_button, press_button);
+/// Which buttons to press to reach the target lights with the fewest presses.
+fn part1_iter(machine: &Machine, budget: &Budget) -> budget::Outcome<Vec<bool>> {
+    let mut meter = budget.start();
+    let plan = min_weight_solution(&machine.buttons, &machine.lights, &mut meter);
+    return meter.outcome(plan);
+}
+
+fn count_toggles(plan: &[bool]) -> u64 {
+    return plan.iter().filter(|&&p| p).count() as u64;
+}
+
+/// Replays a toggle plan from all lights off and checks it lands on the
+/// target lights.
+fn verify_lights(machine: &Machine, plan: &[bool]) -> bool {
+    let mut lights = vec![false; machine.lights.len()];
+    for (button, &pressed) in machine.buttons.iter().zip(plan) {
+        if pressed {
+            for &l_idx in button {
+                lights[l_idx] = !lights[l_idx];
+            }
+        }
+    }
+    return plan.len() == machine.buttons.len() && lights == machine.lights;
 }
 
-fn part1_iter(machine: &Machine) -> u64 {
-    // println!("{:?}", machine);
-    let light_target = &machine.lights;
-    let light_curr: Vec<bool> = (0..light_target.len()).map(|_| false).collect();
-    let buttons = &machine.buttons;
-    let buttons_pressed = part1_helper(light_target, &light_curr, buttons, 0);
-    return buttons_pressed as u64;
+/// Replays a press plan from zero joltage and checks it lands exactly on the
+/// target joltage.
+fn verify_joltage(machine: &Machine, plan: &[u64]) -> bool {
+    let mut joltage = vec![0u64; machine.joltage.len()];
+    for (button, &presses) in machine.buttons.iter().zip(plan) {
+        for &j_idx in button {
+            joltage[j_idx] += presses;
+        }
+    }
+    let target = machine.joltage.iter().map(|&j| j as u64);
+    return plan.len() == machine.buttons.len() && joltage.into_iter().eq(target);
 }
 
-fn part2_iter_lp(machine: &Machine) -> u64 {
+#[cfg(feature = "lpsolve")]
+fn part2_iter_lp(machine: &Machine) -> Option<Vec<u64>> {
     let (joltage, buttons) = (&machine.joltage, &machine.buttons);
     let (m, n) = (machine.joltage.len(), buttons.len());
     // variables! {problem: x[n] (integer) >= 0;}
@@ -103,22 +161,319 @@ fn part2_iter_lp(machine: &Machine) -> u64 {
         model = model.with(constr);
         // sum_{j} button_presses[j] * button_action[i,j] = joltage[i]
     }
-    let opt_obj = model.solve().unwrap().eval(&objective);
-    return opt_obj as u64;
+    let solution = model.solve().ok()?;
+    return Some(
+        button_presses
+            .iter()
+            .map(|&v| solution.value(v).round() as u64)
+            .collect(),
+    );
+}
+
+/// Solves the joltage system exactly without an external LP solver. No
+/// button can be pressed more often than the smallest joltage it feeds.
+fn part2_iter_exact(machine: &Machine) -> Option<Vec<u64>> {
+    let (joltage, buttons) = (&machine.joltage, &machine.buttons);
+    let (m, n) = (joltage.len(), buttons.len());
+    let mut action: Array2<i64> = Array2::zeros((m, n));
+    for (b_idx, b) in buttons.iter().enumerate() {
+        for &j_idx in b {
+            action[[j_idx, b_idx]] = 1;
+        }
+    }
+    let target: Vec<i64> = joltage.iter().map(|&j| j as i64).collect();
+    let upper: Vec<i64> = buttons
+        .iter()
+        .map(|b| b.iter().map(|&j_idx| target[j_idx]).min().unwrap_or(0))
+        .collect();
+    let presses = min_sum_solution(action.view(), &target, &upper)?;
+    return Some(presses.iter().map(|&p| p as u64).collect());
+}
+
+/// How many times to press each button to reach the target joltage with the
+/// fewest presses.
+fn part2_iter(machine: &Machine) -> Option<Vec<u64>> {
+    #[cfg(feature = "lpsolve")]
+    {
+        let presses = part2_iter_lp(machine);
+        debug_assert_eq!(
+            presses.as_ref().map(|p| p.iter().sum::<u64>()),
+            part2_iter_exact(machine).map(|p| p.iter().sum::<u64>())
+        );
+        return presses;
+    }
+    #[cfg(not(feature = "lpsolve"))]
+    return part2_iter_exact(machine);
+}
+
+fn part1(machines: &Vec<Result<Machine, MachineError>>, budget: &Budget) -> Vec<Outcome> {
+    let presses = |machine: &Machine| {
+        return part1_iter(machine, budget).map(|plan| {
+            assert!(verify_lights(machine, &plan), "Plan misses the lights");
+            return count_toggles(&plan);
+        });
+    };
+    return machines.iter().map(|m| solve_machine(m, presses)).collect();
+}
+
+fn part2(machines: &Vec<Result<Machine, MachineError>>) -> Vec<Outcome> {
+    let presses = |machine: &Machine| {
+        let Some(plan) = part2_iter(machine) else {
+            return budget::Outcome::Infeasible;
+        };
+        assert!(verify_joltage(machine, &plan), "Plan misses the joltage");
+        return budget::Outcome::Solved(plan.iter().sum::<u64>());
+    };
+    return machines
+        .iter()
+        .report("Day 10 part 2")
+        .map(|m| solve_machine(m, presses))
+        .collect();
 }
 
-fn part1(machines: &Vec<Machine>) -> u64 {
-    return machines.iter().map(part1_iter).sum();
+fn solve_machine(
+    machine: &Result<Machine, MachineError>,
+    presses: impl Fn(&Machine) -> budget::Outcome<u64>,
+) -> Outcome {
+    return match machine {
+        Ok(machine) => match presses(machine) {
+            budget::Outcome::Solved(n) => Outcome::Solved(n),
+            budget::Outcome::Infeasible => Outcome::Infeasible,
+            budget::Outcome::Unknown(reason) => Outcome::Unknown(reason),
+        },
+        Err(e) => Outcome::Invalid(e.clone()),
+    };
 }
 
-fn part2(machines: &Vec<Machine>) -> u64 {
-    return machines.iter().progress().map(part2_iter_lp).sum();
+/// Totals the presses over all machines, or names every machine that could
+/// not be solved by its line number.
+fn summarize(outcomes: &Vec<Outcome>) -> Result<u64, String> {
+    let mut total = 0;
+    let mut failures = Vec::new();
+    for (idx, outcome) in outcomes.iter().enumerate() {
+        let line = idx + 1;
+        match outcome {
+            Outcome::Solved(presses) => total += presses,
+            Outcome::Infeasible => failures.push(format!("line {line}: infeasible")),
+            Outcome::Unknown(reason) => {
+                failures.push(format!("line {line}: gave up, {}", reason.name()))
+            }
+            Outcome::Invalid(MachineError::Parse(msg)) => {
+                failures.push(format!("line {line}: parse error, {msg}"))
+            }
+            Outcome::Invalid(MachineError::ButtonOutOfRange { button, index }) => failures.push(
+                format!("line {line}: button {button} refers to out of range index {index}"),
+            ),
+        }
+    }
+    if !failures.is_empty() {
+        return Err(failures.join("\n"));
+    }
+    return Ok(total);
+}
+
+fn report(part: u8, outcomes: &Vec<Outcome>) {
+    let total = summarize(outcomes).map_err(|failures| format!("unsolved machines\n{failures}"));
+    output::report(part, total);
 }
 
 pub fn day10(contents: &String) {
     let machines: Vec<_> = contents.lines().map(make_machine).collect();
-    let p1 = part1(&machines);
-    println!("Part 1: {}", p1);
-    let p2 = part2(&machines);
-    println!("Part 2: {}", p2);
+    report(1, &part1(&machines, &Budget::from_env()));
+    report(2, &part2(&machines));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rng::Rng;
+    use std::cmp::min;
+
+    fn part1_helper(
+        light_target: &Vec<bool>,
+        light_curr: &Vec<bool>,
+        buttons: &[Vec<usize>],
+        buttons_pressed: u16,
+    ) -> u16 {
+        if light_target == light_curr {
+            return buttons_pressed;
+        }
+        if buttons.len() == 0 {
+            return u16::MAX;
+        }
+        let button = &buttons[0];
+        let buttons_left = &buttons[1..];
+        let not_press_button =
+            part1_helper(light_target, light_curr, buttons_left, buttons_pressed);
+        let mut light_pressed = light_curr.clone();
+        for &b_idx in button {
+            light_pressed[b_idx] = !light_pressed[b_idx];
+        }
+        let press_button = part1_helper(
+            light_target,
+            &light_pressed,
+            buttons_left,
+            buttons_pressed + 1,
+        );
+        return min(not_press_button, press_button);
+    }
+
+    /// Exhaustive search over every subset of buttons, the reference for
+    /// `part1_iter`.
+    fn part1_recursive(machine: &Machine) -> u64 {
+        let light_target = &machine.lights;
+        let light_curr: Vec<bool> = (0..light_target.len()).map(|_| false).collect();
+        let buttons = &machine.buttons;
+        let buttons_pressed = part1_helper(light_target, &light_curr, buttons, 0);
+        return buttons_pressed as u64;
+    }
+
+    const EXAMPLE: &str = "\
+[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}
+[...#.] (0,2,3,4) (2,3) (0,4) (0,1,2) (1,2,3,4) {7,5,12,7,2}
+[.###.#] (0,1,2,3,4) (0,3,4) (0,1,2,4,5) (1,2) {10,11,11,5,10,5}
+";
+
+    fn random_machine(rng: &mut Rng, num_lights: usize, num_buttons: usize) -> Machine {
+        let lights = (0..num_lights).map(|_| rng.below(2) == 1).collect();
+        let buttons = (0..num_buttons)
+            .map(|_| (0..num_lights).filter(|_| rng.below(3) == 0).collect())
+            .collect();
+        return Machine {
+            lights,
+            buttons,
+            joltage: vec![0; num_lights],
+        };
+    }
+
+    fn fewest_presses(machine: &Machine, budget: &Budget) -> budget::Outcome<u64> {
+        return part1_iter(machine, budget).map(|plan| {
+            assert!(verify_lights(machine, &plan));
+            return count_toggles(&plan);
+        });
+    }
+
+    #[test]
+    fn part1_matches_recursive_on_example() {
+        let machines: Vec<_> = EXAMPLE.lines().map(make_machine).collect();
+        assert_eq!(summarize(&part1(&machines, &Budget::default())), Ok(7));
+        for machine in machines.iter().flatten() {
+            let expected = budget::Outcome::Solved(part1_recursive(machine));
+            assert_eq!(fewest_presses(machine, &Budget::default()), expected);
+        }
+    }
+
+    #[test]
+    fn part1_matches_recursive_on_random_machines() {
+        let mut rng = Rng::new(10);
+        for _ in 0..300 {
+            let (num_lights, num_buttons) = (1 + rng.below(8), rng.below(12));
+            let machine = random_machine(&mut rng, num_lights, num_buttons);
+            let expected = match part1_recursive(&machine) {
+                presses if presses == u16::MAX as u64 => budget::Outcome::Infeasible,
+                presses => budget::Outcome::Solved(presses),
+            };
+            assert_eq!(
+                fewest_presses(&machine, &Budget::default()),
+                expected,
+                "{machine:?}"
+            );
+        }
+    }
+
+    /// Machines too large to search exhaustively give up within the node
+    /// limit instead of allocating or overflowing.
+    #[test]
+    fn part1_gives_up_on_large_machines() {
+        let mut rng = Rng::new(35);
+        let budget = Budget::default().with_node_limit(100_000);
+        for (num_lights, num_buttons) in [(30, 70), (100, 150)] {
+            let machine = random_machine(&mut rng, num_lights, num_buttons);
+            let outcome = fewest_presses(&machine, &budget);
+            assert!(
+                matches!(
+                    outcome,
+                    budget::Outcome::Solved(_) | budget::Outcome::Unknown(Exhausted::Nodes)
+                ),
+                "{outcome:?}"
+            );
+        }
+    }
+
+    /// Lowest total over every press count up to each button's bound.
+    fn part2_brute_force(machine: &Machine) -> Option<u64> {
+        fn press(machine: &Machine, button: usize, joltage: &mut Vec<u64>) -> Option<u64> {
+            if button == machine.buttons.len() {
+                let target = machine.joltage.iter().map(|&j| j as u64);
+                return joltage.iter().copied().eq(target).then_some(0);
+            }
+            let lights = &machine.buttons[button];
+            let upper = lights
+                .iter()
+                .map(|&j| machine.joltage[j] as u64)
+                .min()
+                .unwrap_or(0);
+            let mut best = None;
+            for presses in 0..=upper {
+                if let Some(rest) = press(machine, button + 1, joltage) {
+                    best = Some(best.map_or(presses + rest, |b: u64| b.min(presses + rest)));
+                }
+                for &j in lights {
+                    joltage[j] += 1;
+                }
+            }
+            for &j in lights {
+                joltage[j] -= upper + 1;
+            }
+            return best;
+        }
+        return press(machine, 0, &mut vec![0; machine.joltage.len()]);
+    }
+
+    #[test]
+    fn part2_example() {
+        let machines: Vec<_> = EXAMPLE.lines().map(make_machine).collect();
+        assert_eq!(summarize(&part2(&machines)), Ok(33));
+        let infeasible = make_machine("[..] (0) (0,1) {1,2}").unwrap();
+        assert_eq!(part2_iter(&infeasible), None);
+    }
+
+    #[test]
+    fn part2_matches_brute_force_on_random_machines() {
+        let mut rng = Rng::new(34);
+        for _ in 0..400 {
+            let (num_lights, num_buttons) = (1 + rng.below(4), rng.below(6));
+            let mut machine = random_machine(&mut rng, num_lights, num_buttons);
+            machine.joltage = (0..num_lights).map(|_| rng.below(5) as u16).collect();
+            let presses = part2_iter(&machine).map(|plan| {
+                assert!(verify_joltage(&machine, &plan), "{machine:?}");
+                return plan.iter().sum::<u64>();
+            });
+            assert_eq!(presses, part2_brute_force(&machine), "{machine:?}");
+        }
+    }
+
+    #[test]
+    fn bad_lines_are_named_in_the_summary() {
+        let contents = "\
+[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}
+[.#] (0) (1)
+[.x] (0) (1) {1,1}
+[.#] (0) (1) {1,1,1}
+[.#] (0) (2) {1,1}
+[#.] (1) {1,1}";
+        let machines: Vec<_> = contents.lines().map(make_machine).collect();
+        let outcomes = part1(&machines, &Budget::default());
+        assert_eq!(outcomes[0], Outcome::Solved(2));
+        assert_eq!(
+            summarize(&outcomes),
+            Err(String::from(
+                "line 2: parse error, missing {...} joltage block\n\
+                 line 3: parse error, invalid light `x`\n\
+                 line 4: parse error, 2 lights but 3 joltages\n\
+                 line 5: button 1 refers to out of range index 2\n\
+                 line 6: infeasible"
+            ))
+        );
+    }
 }
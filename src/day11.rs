@@ -1,109 +1,220 @@
-use ndarray::prelude::*;
-use std::collections::HashMap;
+use crate::graph::{Dag, Graph, PathQuery};
+use crate::logging::{debug, warning};
+use crate::output;
+use std::collections::{BTreeSet, HashMap};
 
-type KT = usize;
-type Graph = HashMap<KT, Vec<KT>>;
-
-fn make_keys(contents: &String) -> Option<HashMap<&str, usize>> {
-    let mut keys = HashMap::from_iter(contents.lines().enumerate().map(|(idx, s)| (&s[..3], idx)));
-    keys.insert("out", keys.len());
-    return Some(keys);
+/// Maps device names to dense node ids and back.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Interner<'a> {
+    ids: HashMap<&'a str, usize>,
+    names: Vec<&'a str>,
 }
 
-fn create_adjacency(graph: &Graph) -> Option<Array2<u64>> {
-    let n_nodes = graph.len();
-    let mut adj: Array2<u64> = Array2::default((n_nodes, n_nodes));
-    for (&k, v) in graph.iter() {
-        for &idx in v {
-            adj[[k, idx]] = 1;
+impl<'a> Interner<'a> {
+    fn intern(&mut self, name: &'a str) -> usize {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
         }
+        let id = self.names.len();
+        self.ids.insert(name, id);
+        self.names.push(name);
+        return id;
+    }
+
+    fn get(&self, name: &str) -> Result<usize, String> {
+        return self
+            .ids
+            .get(name)
+            .copied()
+            .ok_or_else(|| format!("unknown device `{name}`"));
+    }
+
+    fn name(&self, id: usize) -> &'a str {
+        return self.names[id];
+    }
+
+    fn len(&self) -> usize {
+        return self.names.len();
     }
-    return Some(adj);
 }
 
-fn create_key_value(keys: &HashMap<&str, usize>, line: &str) -> Option<(KT, Vec<KT>)> {
-    let mut sp = line.split_whitespace();
-    let key = *keys.get(&sp.next().unwrap()[..3])?;
-    let val: Vec<_> = sp.map(|s| *keys.get(s).unwrap()).collect();
-    return Some((key, val));
+/// Problems found while reading the device list, reported by line number.
+#[derive(Debug, Clone, PartialEq)]
+enum ParseIssue {
+    Malformed { line: usize, text: String },
+    DuplicateSource { line: usize, name: String },
 }
 
-fn make_graph(contents: &String) -> Option<(HashMap<&str, usize>, Graph)> {
-    let keys = make_keys(contents)?;
-    let mut graph = Graph::from_iter(
-        contents
-            .lines()
-            .map(|line| create_key_value(&keys, line).unwrap()),
-    );
-    graph.insert(keys.len() - 1, Vec::new());
-    return Some((keys, graph));
+fn is_valid_name(name: &str) -> bool {
+    return !name.is_empty() && !name.contains(|c: char| c == ':' || c.is_whitespace());
 }
 
-/// Returns number of paths from curr_node to target_node
-fn depth_first_helper(
-    graph: &Graph,
-    curr_node: KT,
-    visited: &mut Vec<bool>,
-    target_node: KT,
-) -> Option<u64> {
-    if curr_node == target_node {
-        return Some(1);
+/// Parses `name: out1 out2 ...` lines. Names may be any length, devices may
+/// appear only as outputs, and repeated source lines are merged. Malformed
+/// lines are skipped and reported.
+fn make_graph(contents: &String) -> (Interner<'_>, Graph, Vec<ParseIssue>) {
+    let mut keys = Interner::default();
+    let mut edges: HashMap<usize, BTreeSet<usize>> = HashMap::new();
+    let mut issues = Vec::new();
+    for (idx, text) in contents.lines().enumerate() {
+        let line = idx + 1;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let Some((source, outputs)) = text.split_once(':') else {
+            issues.push(ParseIssue::Malformed {
+                line,
+                text: String::from(text),
+            });
+            continue;
+        };
+        let (source, outputs): (&str, Vec<&str>) =
+            (source.trim(), outputs.split_whitespace().collect());
+        if !is_valid_name(source) || !outputs.iter().all(|o| is_valid_name(o)) {
+            issues.push(ParseIssue::Malformed {
+                line,
+                text: String::from(text),
+            });
+            continue;
+        }
+        let key = keys.intern(source);
+        if edges.contains_key(&key) {
+            issues.push(ParseIssue::DuplicateSource {
+                line,
+                name: String::from(source),
+            });
+        }
+        let val = edges.entry(key).or_default();
+        for output in outputs {
+            val.insert(keys.intern(output));
+        }
     }
-    visited[curr_node] = true;
-    let neighbors = graph.get(&curr_node)?;
-    let mut ways = 0;
-    for &neighbor in neighbors {
-        if !visited[neighbor] {
-            let way = depth_first_helper(graph, neighbor, visited, target_node);
-            println!("{}, {}, {}", curr_node, neighbor, target_node);
-            ways += way.unwrap();
+    let mut graph = Graph::new(keys.len());
+    for (key, val) in edges {
+        for idx in val {
+            graph.add_edge(key, idx);
         }
     }
-    visited[curr_node] = false;
-    return Some(ways);
+    return (keys, graph, issues);
 }
 
-fn part1(keys: &HashMap<&str, usize>, graph: &Graph) -> Option<u64> {
-    let node0 = *keys.get("you")?;
-    let node1 = *keys.get("out")?;
-    let mut visited = (0..keys.len()).map(|_| false).collect();
-    let ways1 = depth_first_helper(graph, node0, &mut visited, node1)?;
-    return Some(ways1);
+/// Counts paths between named devices that pass through every device in
+/// `required` and avoid every device in `forbidden`.
+fn count_named_paths(
+    keys: &Interner,
+    graph: &Dag,
+    start: &str,
+    end: &str,
+    required: &[&str],
+    forbidden: &[&str],
+) -> Result<u64, String> {
+    let lookup = |names: &[&str]| -> Result<Vec<usize>, String> {
+        names.iter().map(|name| keys.get(name)).collect()
+    };
+    let query = PathQuery {
+        start: keys.get(start)?,
+        end: keys.get(end)?,
+        required: lookup(required)?,
+        forbidden: lookup(forbidden)?,
+        ordered: false,
+    };
+    return graph
+        .count_query(&query)
+        .ok_or_else(|| String::from("path count overflowed"));
 }
 
-fn distance_linalg(adj: ArrayView2<u64>, start: usize, end: usize) -> Option<u64> {
-    let mut v_old = Array1::from_iter((0..adj.shape()[0]).map(|_| 0));
-    let mut v_new = v_old;
-    loop {
-        v_old = v_new;
-        v_new = adj.dot(&v_old);
-        v_new[[end]] = 1;
-        if v_old == v_new {
-            break;
-        }
-    }
-    return Some(v_old[start]);
+fn part1(keys: &Interner, graph: &Dag) -> Result<u64, String> {
+    let node0 = keys.get("you")?;
+    let node1 = keys.get("out")?;
+    return graph
+        .count_paths(node0, node1)
+        .ok_or_else(|| String::from("path count overflowed"));
 }
 
-fn part2_linalg(keys: &HashMap<&str, usize>, graph: &Graph) -> Option<u64> {
-    let adj = create_adjacency(graph)?;
-    let svr = *keys.get("svr")?;
-    let dac = *keys.get("dac")?;
-    let fft = *keys.get("fft")?;
-    let out = *keys.get("out")?;
-    let svr_dac = distance_linalg(adj.view(), svr, dac)?;
-    let dac_fft = distance_linalg(adj.view(), dac, fft)?;
-    let fft_out = distance_linalg(adj.view(), fft, out)?;
-    let svr_fft = distance_linalg(adj.view(), svr, fft)?;
-    let fft_dac = distance_linalg(adj.view(), fft, dac)?;
-    let dac_out = distance_linalg(adj.view(), dac, out)?;
-    return Some(svr_dac * dac_fft * fft_out + svr_fft * fft_dac * dac_out);
+fn part2(keys: &Interner, graph: &Dag) -> Result<u64, String> {
+    return count_named_paths(keys, graph, "svr", "out", &["dac", "fft"], &[]);
 }
 
 pub fn day11(contents: &String) {
-    let (keys, graph) = make_graph(contents).unwrap();
-    let p1 = part1(&keys, &graph).unwrap();
-    println!("Part 1: {:?}", p1);
-    let p2 = part2_linalg(&keys, &graph).unwrap();
-    println!("Part 2: {:?}", p2);
+    let (keys, graph, issues) = make_graph(contents);
+    for issue in issues {
+        match issue {
+            ParseIssue::Malformed { line, text } => {
+                warning!("Skipping malformed line {line}: `{text}`")
+            }
+            ParseIssue::DuplicateSource { line, name } => {
+                warning!("Line {line} repeats device `{name}`, merging its outputs")
+            }
+        }
+    }
+    debug!("{} devices", keys.len());
+    let graph = match graph.into_dag() {
+        Ok(graph) => graph,
+        Err(cycle) => {
+            let names: Vec<_> = cycle.iter().map(|&id| keys.name(id)).collect();
+            let message = format!("device graph has a cycle through {}", names.join(" -> "));
+            output::report(1, Err::<u64, _>(&message));
+            output::report(2, Err::<u64, _>(&message));
+            return;
+        }
+    };
+    output::report(1, part1(&keys, &graph));
+    output::report(2, part2(&keys, &graph));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn make_graph_interns_names_and_reports_issues() {
+        let contents = String::from(
+            "you: router out\n\
+             router: switchboard\n\
+             bad line\n\
+             you: switchboard\n\
+             a b: out\n\
+             : out\n\
+             switchboard: out x:y\n",
+        );
+        let (keys, graph, issues) = make_graph(&contents);
+        assert_eq!(keys.names, ["you", "router", "out", "switchboard"]);
+        assert_eq!(
+            issues,
+            [
+                ParseIssue::Malformed {
+                    line: 3,
+                    text: String::from("bad line")
+                },
+                ParseIssue::DuplicateSource {
+                    line: 4,
+                    name: String::from("you")
+                },
+                ParseIssue::Malformed {
+                    line: 5,
+                    text: String::from("a b: out")
+                },
+                ParseIssue::Malformed {
+                    line: 6,
+                    text: String::from(": out")
+                },
+                ParseIssue::Malformed {
+                    line: 7,
+                    text: String::from("switchboard: out x:y")
+                },
+            ]
+        );
+        let mut outputs = graph.neighbors(0).to_vec();
+        outputs.sort();
+        assert_eq!(outputs, [1, 2, 3]);
+        assert!(graph.neighbors(2).is_empty());
+        assert!(graph.neighbors(3).is_empty());
+        assert_eq!(keys.get("switchboard"), Ok(3));
+        assert_eq!(keys.get("x"), Err(String::from("unknown device `x`")));
+        let graph = graph.into_dag().unwrap();
+        assert_eq!(
+            part2(&keys, &graph),
+            Err(String::from("unknown device `svr`"))
+        );
+    }
 }
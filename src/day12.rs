@@ -1,16 +1,17 @@
-use indicatif::ProgressIterator;
-use itertools::Itertools;
+use crate::budget::{Budget, Outcome};
+use crate::logging::{debug, info, warning};
+use crate::output::{self, Status};
+use crate::packing::{self, Engine, Filter, Placement, Problem};
+use crate::progress::ReportProgress;
 use ndarray::prelude::*;
 use regex::Regex;
+use std::collections::BTreeMap;
 
-const GIFT_SHAPE: (usize, usize) = (3, 3);
-
+/// A present shape with every distinct orientation under rotation and
+/// reflection.
 #[derive(Debug, PartialEq, Clone)]
 struct Gift {
-    north: Array2<bool>,
-    east: Array2<bool>,
-    south: Array2<bool>,
-    west: Array2<bool>,
+    orientations: Vec<Array2<bool>>,
     num_occupied: u64,
 }
 
@@ -29,22 +30,69 @@ fn rotate_once(gift: &Array2<bool>) -> Array2<bool> {
     return arr;
 }
 
-fn create_gift(gift_str: &str) -> Option<Gift> {
+fn flip(gift: &Array2<bool>) -> Array2<bool> {
+    return gift.slice(s![.., ..;-1]).to_owned();
+}
+
+/// The orbit of `gift` under the 8-element dihedral group, without
+/// duplicates for symmetric shapes.
+fn all_orientations(gift: &Array2<bool>) -> Vec<Array2<bool>> {
+    let mut ret: Vec<Array2<bool>> = Vec::new();
+    for mut orientation in [gift.clone(), flip(gift)] {
+        for _ in 0..4 {
+            if !ret.contains(&orientation) {
+                ret.push(orientation.clone());
+            }
+            orientation = rotate_once(&orientation);
+        }
+    }
+    return ret;
+}
+
+/// Crops `gift` to the bounding box of its occupied cells. Returns `None` if
+/// nothing is occupied.
+fn trim_gift(gift: &Array2<bool>) -> Option<Array2<bool>> {
+    let occupied_rows: Vec<_> = (0..gift.nrows())
+        .filter(|&i| gift.row(i).iter().any(|&b| b))
+        .collect();
+    let occupied_cols: Vec<_> = (0..gift.ncols())
+        .filter(|&j| gift.column(j).iter().any(|&b| b))
+        .collect();
+    let (row_min, row_max) = (*occupied_rows.first()?, *occupied_rows.last()?);
+    let (col_min, col_max) = (*occupied_cols.first()?, *occupied_cols.last()?);
+    return Some(
+        gift.slice(s![row_min..=row_max, col_min..=col_max])
+            .to_owned(),
+    );
+}
+
+/// Parses an `N:` block of any size into its index `N` and shape. Shorter
+/// rows are padded with empty cells before the shape is cropped to its
+/// bounding box.
+fn create_gift(gift_str: &str) -> Option<(usize, Gift)> {
     let mut lines = gift_str.lines();
-    lines.next();
-    let it = lines.map(|l| l.chars().map(|c| c == '#')).flatten();
-    let north = Array2::from_shape_vec(GIFT_SHAPE, it.collect()).ok()?;
-    let east = rotate_once(&north);
-    let south = rotate_once(&east);
-    let west = rotate_once(&south);
-    let num_occupied = count_array(&north);
-    return Some(Gift {
-        north,
-        east,
-        south,
-        west,
-        num_occupied,
+    let idx = lines
+        .next()?
+        .trim()
+        .strip_suffix(':')?
+        .parse::<usize>()
+        .ok()?;
+    let rows: Vec<Vec<bool>> = lines
+        .map(|l| l.trim_end().chars().map(|c| c == '#').collect())
+        .collect();
+    let num_cols = rows.iter().map(|r| r.len()).max()?;
+    let padded = Array2::from_shape_fn((rows.len(), num_cols), |(i, j)| {
+        rows[i].get(j).copied().unwrap_or(false)
     });
+    let north = trim_gift(&padded)?;
+    let num_occupied = count_array(&north);
+    return Some((
+        idx,
+        Gift {
+            orientations: all_orientations(&north),
+            num_occupied,
+        },
+    ));
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -54,15 +102,16 @@ struct Tree {
     requirements: Vec<u64>,
 }
 
+/// Parses `WxH: counts...`, a region `W` cells wide and `H` cells tall.
 fn create_tree(tree_str: &str) -> Option<Tree> {
     let re = Regex::new(r"\d+").ok()?;
     let nums: Vec<_> = re.find_iter(tree_str).collect();
-    let rows = nums[0].as_str().parse::<usize>().ok()?;
-    let cols = nums[1].as_str().parse::<usize>().ok()?;
+    let cols = nums.first()?.as_str().parse::<usize>().ok()?;
+    let rows = nums.get(1)?.as_str().parse::<usize>().ok()?;
     let requirements: Vec<_> = nums[2..]
         .iter()
-        .map(|m| m.as_str().parse::<u64>().unwrap())
-        .collect();
+        .map(|m| m.as_str().parse::<u64>().ok())
+        .collect::<Option<_>>()?;
     return Some(Tree {
         rows,
         cols,
@@ -70,11 +119,6 @@ fn create_tree(tree_str: &str) -> Option<Tree> {
     });
 }
 
-fn is_piece_fit(insert_area: ArrayView2<bool>, gift: ArrayView2<bool>) -> bool {
-    assert!(insert_area.shape() == gift.shape());
-    return !insert_area.iter().zip(gift).any(|(&a, &b)| a && b);
-}
-
 fn is_valid_num_occupied(gifts: &Vec<Gift>, tree: &Tree) -> bool {
     let tree_numel = (tree.rows * tree.cols) as u64;
     let total_num_occupied: u64 = gifts
@@ -85,120 +129,355 @@ fn is_valid_num_occupied(gifts: &Vec<Gift>, tree: &Tree) -> bool {
     return total_num_occupied <= tree_numel;
 }
 
-fn get_orientation(gift: &Gift, idx: u8) -> Option<&Array2<bool>> {
-    return match idx {
-        0 => Some(&gift.north),
-        1 => Some(&gift.east),
-        2 => Some(&gift.south),
-        3 => Some(&gift.west),
-        _ => None,
-    };
+/// What settled whether a tree's presents fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Decider {
+    Area,
+    Filter(Filter),
+    Backend(Engine),
 }
 
-fn fit_under_tree_loop(
-    row_idx: usize,
-    col_idx: usize,
-    gifts: &Vec<Gift>,
-    gift_arr: ArrayView2<bool>,
-    state: ArrayView2<bool>,
-    mut new_state: ArrayViewMut2<bool>,
-    requirements: &mut Vec<u64>,
-) -> Option<bool> {
-    let row_bound = row_idx + gift_arr.shape()[0];
-    let col_bound = col_idx + gift_arr.shape()[1];
-    let slice_idxs = s![row_idx..row_bound, col_idx..col_bound];
-    let slice: ArrayView2<bool> = state.slice(slice_idxs);
-    let piece_fits = is_piece_fit(slice, gift_arr);
-    if piece_fits {
-        let mut slice_mut: ArrayViewMut2<bool> = new_state.slice_mut(slice_idxs);
-        for ((i, j), b) in gift_arr.indexed_iter() {
-            slice_mut[[i, j]] |= b;
-        }
-        let state_view = new_state.view();
-        let all_fit = fit_under_tree_helper(gifts, state_view, requirements)?;
-        if all_fit {
-            return Some(true);
-        }
-        let mut slice_mut: ArrayViewMut2<bool> = new_state.slice_mut(slice_idxs);
-        slice_mut.assign(&slice);
-    }
-    return Some(false);
-}
-
-fn fit_under_tree_helper(
-    gifts: &Vec<Gift>,
-    state: ArrayView2<bool>,
-    requirements: &mut Vec<u64>,
-) -> Option<bool> {
-    let first_idx = requirements.iter().find_position(|&&x| x != 0);
-    if first_idx.is_none() {
-        return Some(true);
-    }
-    let next_gift = first_idx.unwrap().0;
-    requirements[next_gift] -= 1;
-    let gift = &gifts[next_gift];
-    let mut new_state: Array2<bool> = state.to_owned();
-    for orientation in 0..3 {
-        let gift_arr = get_orientation(gift, orientation)?;
-        let max_row = state.shape()[0] - gift_arr.shape()[0] + 1;
-        let max_col = state.shape()[1] - gift_arr.shape()[1] + 1;
-        // println!("{}: {:?}", orientation, (max_row, max_col));
-        for row_idx in 0..max_row {
-            for col_idx in 0..max_col {
-                let fits = fit_under_tree_loop(
-                    row_idx,
-                    col_idx,
-                    gifts,
-                    gift_arr.view(),
-                    state,
-                    new_state.view_mut(),
-                    requirements,
-                )?;
-                if fits {
-                    return Some(true);
-                }
-            }
-        }
+impl Decider {
+    fn name(self) -> &'static str {
+        return match self {
+            Decider::Area => "area",
+            Decider::Filter(filter) => filter.name(),
+            Decider::Backend(engine) => engine.name(),
+        };
+    }
+}
+
+/// The packing found, if any, and what settled it.
+#[derive(Debug, Clone, PartialEq)]
+struct Verdict {
+    outcome: Outcome<Vec<Placement>>,
+    decided_by: Decider,
+}
+
+impl Verdict {
+    fn fits(&self) -> bool {
+        return self.outcome.solved().is_some();
     }
-    requirements[next_gift] += 1;
-    return Some(false);
 }
 
-fn fit_under_tree(tree: &Tree, gifts: &Vec<Gift>) -> Option<bool> {
+/// How searches are run once the filters have passed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Backend {
+    Single(Engine),
+    /// Every engine at once, keeping the first answer
+    Race,
+}
+
+/// Decides whether the tree's requirements can all be packed under it.
+fn fit_under_tree(tree: &Tree, gifts: &Vec<Gift>, backend: Backend, budget: &Budget) -> Verdict {
     if !is_valid_num_occupied(gifts, tree) {
-        return Some(false);
+        return Verdict {
+            outcome: Outcome::Infeasible,
+            decided_by: Decider::Area,
+        };
+    }
+    let shapes: Vec<_> = gifts.iter().map(|g| g.orientations.clone()).collect();
+    let (rows, cols) = (tree.rows, tree.cols);
+    let problem = || Problem::new(rows, cols, &shapes, &tree.requirements);
+    let verdict = match packing::prefilter(rows, cols, &shapes, &tree.requirements) {
+        Some((placements, filter)) => Verdict {
+            outcome: placements.map_or(Outcome::Infeasible, Outcome::Solved),
+            decided_by: Decider::Filter(filter),
+        },
+        None => {
+            let (outcome, engine) = match backend {
+                Backend::Single(engine) => (packing::solve(&problem(), engine, budget), engine),
+                Backend::Race => packing::race(&problem(), budget),
+            };
+            Verdict {
+                outcome,
+                decided_by: Decider::Backend(engine),
+            }
+        }
+    };
+    if let Some(placements) = verdict.outcome.solved() {
+        if let Err(e) = packing::validate(rows, cols, &shapes, &tree.requirements, placements) {
+            panic!("Invalid packing for {rows}x{cols}: {e}");
+        }
     }
-    let tree_size = (tree.rows, tree.cols);
-    let tree_field: Array2<bool> = Array2::from_shape_fn(tree_size, |_| false);
-    let mut requirements = tree.requirements.clone();
-    return fit_under_tree_helper(gifts, tree_field.view(), &mut requirements);
+    return verdict;
 }
 
+/// Each gift lands at its `N:` index, so the blocks may come in any order.
+/// Returns `None` if an index repeats or is missing.
 fn process_contents(contents: &String) -> Option<(Vec<Gift>, Vec<Tree>)> {
-    let sp: Vec<_> = contents.split("\n\n").collect();
-    let gifts: Vec<_> = sp[..sp.len() - 1]
-        .iter()
-        .map(|&g| create_gift(g).unwrap())
-        .collect();
-    let trees = sp
-        .last()?
-        .split("\n")
-        .map(|x| create_tree(x).unwrap())
-        .collect();
+    let mut gifts: Vec<Option<Gift>> = Vec::new();
+    let mut trees = Vec::new();
+    for block in contents.split("\n\n").map(|b| b.trim_matches('\n')) {
+        let is_gift = block
+            .lines()
+            .next()
+            .is_some_and(|l| l.trim_end().ends_with(':'));
+        if is_gift {
+            let (idx, gift) = create_gift(block)?;
+            if gifts.len() <= idx {
+                gifts.resize(idx + 1, None);
+            }
+            if gifts[idx].replace(gift).is_some() {
+                return None;
+            }
+        } else {
+            for line in block.lines().filter(|l| !l.trim().is_empty()) {
+                trees.push(create_tree(line)?);
+            }
+        }
+    }
+    let gifts = gifts.into_iter().collect::<Option<Vec<_>>>()?;
     return Some((gifts, trees));
 }
 
-fn part1(contents: &String) -> Option<u64> {
-    let (gifts, trees) = process_contents(contents)?;
-    let ret = trees
+fn part1(gifts: &Vec<Gift>, trees: &[Tree], backend: Backend, budget: &Budget) -> Vec<Verdict> {
+    return trees
         .iter()
-        .progress()
-        .map(|tree| fit_under_tree(tree, &gifts).unwrap() as u64)
-        .sum();
-    return Some(ret);
+        .report("Day 12 regions")
+        .map(|tree| fit_under_tree(tree, gifts, backend, budget))
+        .collect();
+}
+
+/// Draws every packing found, one region after another.
+fn render_packings(gifts: &[Gift], trees: &[Tree], verdicts: &[Verdict]) -> String {
+    let shapes: Vec<_> = gifts.iter().map(|g| g.orientations.clone()).collect();
+    let mut ret = String::new();
+    for (idx, (tree, verdict)) in trees.iter().zip(verdicts).enumerate() {
+        let Some(placements) = verdict.outcome.solved() else {
+            continue;
+        };
+        ret += &format!("Region {idx}: {}x{}\n", tree.cols, tree.rows);
+        ret += &packing::render(tree.rows, tree.cols, &shapes, placements);
+        ret.push('\n');
+    }
+    return ret;
+}
+
+/// Packing backend named by the `DAY12_BACKEND` environment variable, DLX
+/// by default. `race` runs every engine at once.
+fn backend_from_env() -> Backend {
+    let Ok(name) = std::env::var("DAY12_BACKEND") else {
+        return Backend::Single(Engine::Dlx);
+    };
+    if name == "race" {
+        return Backend::Race;
+    }
+    return Backend::Single(Engine::from_name(&name).unwrap_or_else(|| {
+        let names: Vec<_> = Engine::ALL.iter().map(|e| e.name()).collect();
+        panic!(
+            "Unknown DAY12_BACKEND `{name}`, expected race or one of {}",
+            names.join(", ")
+        )
+    }));
 }
 
 pub fn day12(contents: &String) {
-    let p1 = part1(contents);
-    println!("Part 1: {:?}", p1);
+    let Some((gifts, trees)) = process_contents(contents) else {
+        output::record(1, "could not parse the input", Status::Error);
+        return;
+    };
+    let verdicts = part1(&gifts, &trees, backend_from_env(), &Budget::from_env());
+    let p1 = verdicts.iter().filter(|v| v.fits()).count() as u64;
+    let all_known = verdicts.iter().all(|v| v.outcome.is_known());
+    output::record(
+        1,
+        p1,
+        if all_known {
+            Status::Ok
+        } else {
+            Status::Incomplete
+        },
+    );
+    let mut tally: BTreeMap<Decider, (u64, u64)> = BTreeMap::new();
+    for (idx, verdict) in verdicts.iter().enumerate() {
+        if let Outcome::Unknown(reason) = verdict.outcome {
+            warning!("Region {idx}: gave up, {}", reason.name());
+            continue;
+        }
+        let fits = if verdict.fits() {
+            "fits"
+        } else {
+            "does not fit"
+        };
+        debug!("Region {idx}: {fits} by {}", verdict.decided_by.name());
+        let (fit, no_fit) = tally.entry(verdict.decided_by).or_default();
+        *(if verdict.fits() { fit } else { no_fit }) += 1;
+    }
+    output::export("day12_packings.txt", || {
+        render_packings(&gifts, &trees, &verdicts)
+    });
+    for (decider, (fit, no_fit)) in tally {
+        info!("Decided by {}: {fit} fit, {no_fit} do not", decider.name());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rng::Rng;
+
+    const EXAMPLE: &str = "\
+0:
+###
+##.
+##.
+
+1:
+###
+##.
+.##
+
+2:
+.##
+###
+##.
+
+3:
+##.
+###
+##.
+
+4:
+###
+#..
+###
+
+5:
+###
+.#.
+###
+
+4x4: 0 0 0 0 2 0
+12x5: 1 0 1 0 2 2
+12x5: 1 0 1 0 3 2
+";
+
+    /// Each engine decides every example region within the node limit, the
+    /// SAT engine taking the most nodes, and they agree on which fit. Every
+    /// packing they return must pass `validate`.
+    #[test]
+    fn engines_agree_on_example() {
+        let (gifts, trees) = process_contents(&String::from(EXAMPLE)).unwrap();
+        let shapes: Vec<_> = gifts.iter().map(|g| g.orientations.clone()).collect();
+        let budget = Budget::default().with_node_limit(500_000);
+        for engine in Engine::ALL {
+            let mut fits = Vec::new();
+            for tree in &trees {
+                let (rows, cols, counts) = (tree.rows, tree.cols, &tree.requirements);
+                let problem = Problem::new(rows, cols, &shapes, counts);
+                match packing::solve(&problem, engine, &budget) {
+                    Outcome::Solved(placements) => {
+                        let valid = packing::validate(rows, cols, &shapes, counts, &placements);
+                        assert_eq!(valid, Ok(()), "{}", engine.name());
+                        fits.push(true);
+                    }
+                    Outcome::Infeasible => fits.push(false),
+                    Outcome::Unknown(reason) => {
+                        panic!("{} gave up, {}", engine.name(), reason.name())
+                    }
+                }
+            }
+            assert_eq!(fits, [true, true, false], "{}", engine.name());
+        }
+        let verdicts = part1(&gifts, &trees, Backend::Race, &budget);
+        assert_eq!(verdicts.iter().filter(|v| v.fits()).count(), 2);
+    }
+
+    #[test]
+    fn regions_are_width_by_height() {
+        let (gifts, trees) = process_contents(&String::from(EXAMPLE)).unwrap();
+        let backend = Backend::Single(Engine::Bitboard);
+        let verdicts = part1(&gifts, &trees, backend, &Budget::default());
+        let rendered = render_packings(&gifts, &trees, &verdicts);
+        let region = rendered.split("\n\n").nth(1).unwrap();
+        let mut lines = region.lines();
+        assert_eq!(lines.next(), Some("Region 1: 12x5"));
+        assert!(lines.all(|l| l.len() == 12));
+        assert_eq!(region.lines().count(), 1 + 5);
+    }
+
+    #[test]
+    fn gifts_are_stored_at_their_index() {
+        let contents = String::from("1:\n##\n\n0:\n#\n\n2x2: 1 1\n");
+        let (gifts, _) = process_contents(&contents).unwrap();
+        assert_eq!(
+            gifts.iter().map(|g| g.num_occupied).collect::<Vec<_>>(),
+            [1, 2]
+        );
+        for bad in ["0:\n#\n\n0:\n##\n", "1:\n#\n"] {
+            assert_eq!(process_contents(&String::from(bad)), None, "{bad:?}");
+        }
+    }
+
+    /// Every orientation of a random shape within a 3x3 box.
+    fn random_shape(rng: &mut Rng) -> Vec<Array2<bool>> {
+        loop {
+            let cells = Array2::from_shape_fn((3, 3), |_| rng.below(2) == 0);
+            if let Some(north) = trim_gift(&cells) {
+                return all_orientations(&north);
+            }
+        }
+    }
+
+    /// Whenever the filters rule a small region out, no engine can pack it,
+    /// and every slot layout they return is a valid packing.
+    #[test]
+    fn prefilter_agrees_with_every_engine() {
+        let mut rng = Rng::new(45);
+        let budget = Budget::default().with_node_limit(100_000);
+        let mut decided: BTreeMap<Filter, u64> = BTreeMap::new();
+        for _ in 0..1000 {
+            let shapes: Vec<_> = (0..1 + rng.below(3))
+                .map(|_| random_shape(&mut rng))
+                .collect();
+            let (rows, cols) = (1 + rng.below(5), 1 + rng.below(5));
+            // Add pieces while their area fits, so the colour filters bite
+            let mut counts = vec![0u64; shapes.len()];
+            let mut area = 0;
+            for _ in 0..8 {
+                let shape = rng.below(shapes.len());
+                let size = count_array(&shapes[shape][0]);
+                if area + size <= (rows * cols) as u64 {
+                    area += size;
+                    counts[shape] += 1;
+                }
+            }
+            let Some((placements, filter)) = packing::prefilter(rows, cols, &shapes, &counts)
+            else {
+                continue;
+            };
+            *decided.entry(filter).or_default() += 1;
+            match placements {
+                Some(placements) => {
+                    assert_eq!(filter, Filter::Slots);
+                    let valid = packing::validate(rows, cols, &shapes, &counts, &placements);
+                    assert_eq!(valid, Ok(()));
+                }
+                None => {
+                    let problem = Problem::new(rows, cols, &shapes, &counts);
+                    for engine in Engine::ALL {
+                        let outcome = packing::solve(&problem, engine, &budget);
+                        assert_eq!(
+                            outcome,
+                            Outcome::Infeasible,
+                            "{} packs {rows}x{cols} {counts:?} ruled out by {}",
+                            engine.name(),
+                            filter.name()
+                        );
+                    }
+                }
+            }
+        }
+        let filters = [
+            Filter::Slots,
+            Filter::Bounds,
+            Filter::Checkerboard,
+            Filter::Stripes,
+        ];
+        assert!(
+            filters.iter().all(|f| decided.contains_key(f)),
+            "{decided:?}"
+        );
+    }
 }
@@ -1,3 +1,5 @@
+use crate::output;
+use crate::progress::ReportProgress;
 use pcre2::bytes::Regex;
 
 fn iter_id(id: String, re: &Regex) -> i64 {
@@ -25,7 +27,7 @@ fn process_range(range: &str, re: &Regex) -> i64 {
 fn part1(ranges: &Vec<&str>) -> i64 {
     let mut ret = 0;
     let re = Regex::new(r"^(?P<dd>[1-9]\d*)\g{-1}$").unwrap();
-    for range in ranges {
+    for range in ranges.iter().report("Day 2 part 1") {
         ret += process_range(range, &re);
     }
     return ret;
@@ -34,7 +36,7 @@ fn part1(ranges: &Vec<&str>) -> i64 {
 fn part2(ranges: &Vec<&str>) -> i64 {
     let mut ret = 0;
     let re = Regex::new(r"^(?P<dd>[1-9]\d*)\g{-1}+$").unwrap();
-    for range in ranges {
+    for range in ranges.iter().report("Day 2 part 2") {
         ret += process_range(range, &re);
     }
     return ret;
@@ -43,7 +45,7 @@ fn part2(ranges: &Vec<&str>) -> i64 {
 pub fn day2(contents: &String) {
     let ranges: Vec<&str> = contents.split(",").map(|x| x.trim()).collect();
     let p1 = part1(&ranges);
-    println!("Part 1: {p1}");
+    output::answer(1, p1);
     let p2 = part2(&ranges);
-    println!("Part 2: {p2}");
+    output::answer(2, p2);
 }
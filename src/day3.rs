@@ -1,3 +1,5 @@
+use crate::logging::debug;
+use crate::output;
 use std::cmp::max;
 
 fn p1_process_bank(ints: &Vec<u64>) -> u64 {
@@ -13,7 +15,7 @@ fn p1_process_bank(ints: &Vec<u64>) -> u64 {
             next_max = *x;
         }
     }
-    // println!("{curr_max}, {next_max}");
+    debug!("bank digits {curr_max} then {next_max}");
     return curr_max * 10 + next_max;
 }
 const LIST_LEN: usize = 100;
@@ -91,7 +93,7 @@ pub fn day3(contents: &String) {
         })
         .collect();
     let p1 = part1(&ints);
-    println!("Part 1: {p1}");
+    output::answer(1, p1);
     let p2 = part2(&ints);
-    println!("Part 2: {p2}");
+    output::answer(2, p2);
 }
@@ -1,3 +1,4 @@
+use crate::output;
 use std::cmp::{max, min};
 
 use ndarray::prelude::*;
@@ -59,7 +60,7 @@ fn part2(arr: &Array2<u16>) -> u64 {
 pub fn day4(contents: &String) {
     let arr = process_contents(contents);
     let p1 = part1(&arr);
-    println!("Part 1: {p1}");
+    output::answer(1, p1);
     let p2 = part2(&arr);
-    println!("Part 2: {p2}")
+    output::answer(2, p2)
 }
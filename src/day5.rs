@@ -1,3 +1,4 @@
+use crate::output;
 use std::cmp::max;
 
 fn process_range(range: &str) -> (u64, u64) {
@@ -58,7 +59,7 @@ fn part2(ranges: &Vec<(u64, u64)>) -> u64 {
 pub fn day5(contents: &String) {
     let (ranges, ids) = process_contents(contents);
     let p1 = part1(&ranges, &ids);
-    println!("Part 1: {p1}");
+    output::answer(1, p1);
     let p2 = part2(&ranges);
-    println!("Part 2: {p2}");
+    output::answer(2, p2);
 }
@@ -1,3 +1,4 @@
+use crate::output;
 use std::iter::zip;
 
 use ndarray::prelude::*;
@@ -123,7 +124,7 @@ fn part2(contents: &String) -> u64 {
 pub fn day6(contents: &String) {
     let (arr, ops) = process_contents(contents);
     let p1 = part1(&arr, &ops);
-    println!("Part 1: {p1}");
+    output::answer(1, p1);
     let p2 = part2(contents);
-    println!("Part 2: {p2}");
+    output::answer(2, p2);
 }
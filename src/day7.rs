@@ -1,43 +1,91 @@
+use crate::output;
 use std::collections::HashMap;
 use std::mem::swap;
 
 use ndarray::prelude::*;
 
-fn process_contents(contents: &String) -> (usize, Array2<bool>) {
-    let mut lines = contents.lines();
-    let first_line = lines.next().unwrap();
-    let start_pos = first_line.find('S').unwrap();
-    let num_lines = lines.clone().count();
-    let flat_iter = lines
-        .map(|line| line.chars().map(|x| x == '^'))
-        .flatten()
-        .collect();
-    let arr_shape = (num_lines, first_line.len());
-    let arr: Array2<bool> = Array2::from_shape_vec(arr_shape, flat_iter).unwrap();
-    return (start_pos, arr);
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Component {
+    #[default]
+    Empty,
+    Source,
+    Splitter,
+    MirrorLeft,
+    MirrorRight,
+    Absorber,
+    MultiSplitter,
 }
 
-fn part1(start_pos: usize, arr: &Array2<bool>) -> u64 {
+impl Component {
+    /// Column offsets of the beams leaving this component into the next row.
+    fn outputs(&self) -> &'static [isize] {
+        return match self {
+            Component::Empty | Component::Source => &[0],
+            Component::Splitter => &[-1, 1],
+            Component::MirrorLeft => &[-1],
+            Component::MirrorRight => &[1],
+            Component::Absorber => &[],
+            Component::MultiSplitter => &[-1, 0, 1],
+        };
+    }
+
+    fn is_split(&self) -> bool {
+        return self.outputs().len() > 1;
+    }
+}
+
+fn default_components() -> HashMap<char, Component> {
+    return HashMap::from([
+        ('.', Component::Empty),
+        ('S', Component::Source),
+        ('^', Component::Splitter),
+        ('<', Component::MirrorLeft),
+        ('>', Component::MirrorRight),
+        ('#', Component::Absorber),
+        ('+', Component::MultiSplitter),
+    ]);
+}
+
+fn process_contents(
+    contents: &String,
+    components: &HashMap<char, Component>,
+) -> Option<Array2<Component>> {
+    let lines: Vec<&str> = contents.lines().filter(|l| !l.is_empty()).collect();
+    let row_len = lines.first()?.len();
+    let mut flat_iter = Vec::new();
+    for line in &lines {
+        for c in line.chars() {
+            flat_iter.push(*components.get(&c)?);
+        }
+    }
+    let arr_shape = (lines.len(), row_len);
+    let arr: Array2<Component> = Array2::from_shape_vec(arr_shape, flat_iter).ok()?;
+    return Some(arr);
+}
+
+/// Applies `offset` to `idx`, returning `None` if it leaves the row.
+fn shift(idx: usize, offset: isize, row_len: usize) -> Option<usize> {
+    let next = idx.checked_add_signed(offset)?;
+    return if next < row_len { Some(next) } else { None };
+}
+
+fn part1(arr: &Array2<Component>) -> u64 {
     let row_len = arr.shape()[1];
     let mut curr_beam_row: Array1<bool> = Array1::default((row_len,));
     let mut next_beam_row: Array1<bool> = Array1::default((row_len,));
-    for idx in 0..row_len {
-        curr_beam_row[idx] = idx == start_pos;
-    }
     let mut ret = 0;
     for row in arr.rows() {
-        let overlap = &row & &curr_beam_row;
-        ret += overlap.iter().fold(0u64, |acc, x| acc + (*x as u64));
+        next_beam_row.fill(false);
         for idx in 0..row_len {
-            next_beam_row[idx] = curr_beam_row[idx];
-            if idx > 0 {
-                next_beam_row[idx] |= curr_beam_row[idx - 1] && row[idx - 1];
+            let component = row[idx];
+            if !curr_beam_row[idx] && component != Component::Source {
+                continue;
             }
-            if idx < row_len - 1 {
-                next_beam_row[idx] |= curr_beam_row[idx + 1] && row[idx + 1];
-            }
-            if row[idx] {
-                next_beam_row[idx] = false;
+            ret += component.is_split() as u64;
+            for &offset in component.outputs() {
+                if let Some(next) = shift(idx, offset, row_len) {
+                    next_beam_row[next] = true;
+                }
             }
         }
         swap(&mut next_beam_row, &mut curr_beam_row);
@@ -45,44 +93,125 @@ fn part1(start_pos: usize, arr: &Array2<bool>) -> u64 {
     return ret;
 }
 
-fn part2_helper(
-    curr_pos: usize,
-    arr: &Array2<bool>,
-    row_idx: usize,
-    memos: &mut HashMap<(usize, usize), u64>,
-) -> u64 {
-    if row_idx >= arr.shape()[0] {
-        return 1;
-    }
-    let key = (curr_pos, row_idx);
-    if memos.contains_key(&key) {
-        return *memos.get(&key).unwrap();
+/// Counts timelines bottom-up, one row at a time. Every source starts its own
+/// timeline. Returns `None` if the count overflows a `u128`.
+fn part2(arr: &Array2<Component>) -> Option<u128> {
+    let row_len = arr.shape()[1];
+    let mut curr_counts: Vec<u128> = vec![0; row_len];
+    let mut next_counts: Vec<u128> = vec![0; row_len];
+    for row in arr.rows() {
+        next_counts.fill(0);
+        for idx in 0..row_len {
+            let component = row[idx];
+            let mut count = curr_counts[idx];
+            if component == Component::Source {
+                count = count.checked_add(1)?;
+            }
+            if count == 0 {
+                continue;
+            }
+            for &offset in component.outputs() {
+                if let Some(next) = shift(idx, offset, row_len) {
+                    next_counts[next] = next_counts[next].checked_add(count)?;
+                }
+            }
+        }
+        swap(&mut next_counts, &mut curr_counts);
     }
-    if arr[[row_idx, curr_pos]] {
-        let mut ret = 0;
-        if curr_pos > 0 {
-            ret += part2_helper(curr_pos - 1, arr, row_idx + 1, memos);
+    return curr_counts
+        .iter()
+        .try_fold(0u128, |acc, &x| acc.checked_add(x));
+}
+
+pub fn day7(contents: &String) {
+    let arr = process_contents(contents, &default_components()).expect("Unknown component");
+    let p1 = part1(&arr);
+    output::answer(1, p1);
+    let p2 = part2(&arr).expect("Timeline count overflowed");
+    output::answer(2, p2);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rng::Rng;
+
+    fn part2_helper(
+        curr_pos: usize,
+        arr: &Array2<Component>,
+        row_idx: usize,
+        memos: &mut HashMap<(usize, usize), u64>,
+    ) -> u64 {
+        if row_idx >= arr.shape()[0] {
+            return 1;
+        }
+        let key = (curr_pos, row_idx);
+        if memos.contains_key(&key) {
+            return *memos.get(&key).unwrap();
         }
-        if curr_pos < arr.shape()[1] - 1 {
-            ret += part2_helper(curr_pos + 1, arr, row_idx + 1, memos);
+        let mut ret = 0;
+        for &offset in arr[[row_idx, curr_pos]].outputs() {
+            if let Some(next) = shift(curr_pos, offset, arr.shape()[1]) {
+                ret += part2_helper(next, arr, row_idx + 1, memos);
+            }
         }
         memos.insert(key, ret);
         return ret;
     }
-    let ret = part2_helper(curr_pos, arr, row_idx + 1, memos);
-    memos.insert(key, ret);
-    return ret;
-}
 
-fn part2(start_pos: usize, arr: &Array2<bool>) -> u64 {
-    let mut memos: HashMap<(usize, usize), u64> = HashMap::new();
-    return part2_helper(start_pos, arr, 0, &mut memos);
-}
+    /// Memoized recursive timeline count, kept as a reference for `part2`.
+    fn part2_memo(arr: &Array2<Component>) -> u64 {
+        let mut memos: HashMap<(usize, usize), u64> = HashMap::new();
+        return arr
+            .indexed_iter()
+            .filter(|(_, &c)| c == Component::Source)
+            .map(|((row_idx, col_idx), _)| part2_helper(col_idx, arr, row_idx, &mut memos))
+            .sum();
+    }
 
-pub fn day7(contents: &String) {
-    let (start_pos, arr) = process_contents(contents);
-    let p1 = part1(start_pos, &arr);
-    println!("Part 1: {p1}");
-    let p2 = part2(start_pos, &arr);
-    println!("Part 2: {p2}");
+    const EXAMPLE: &str = "\
+.......S.......
+...............
+.......^.......
+...............
+......^.^......
+...............
+.....^.^.^.....
+...............
+....^.^...^....
+...............
+...^.^...^.^...
+...............
+..^...^.....^..
+...............
+.^.^.^.^.^...^.
+...............
+";
+
+    #[test]
+    fn part2_matches_memo_on_example() {
+        let arr = process_contents(&String::from(EXAMPLE), &default_components()).unwrap();
+        assert_eq!(part2(&arr), Some(40));
+        assert_eq!(part2_memo(&arr), 40);
+    }
+
+    #[test]
+    fn part2_matches_memo_on_random_grids() {
+        let mut rng = Rng::new(7);
+        let symbols: Vec<char> = default_components().into_keys().collect();
+        for _ in 0..200 {
+            let (rows, cols) = (1 + rng.below(12), 1 + rng.below(12));
+            let contents: String = (0..rows)
+                .map(|_| {
+                    let mut line: String = (0..cols)
+                        .map(|_| symbols[rng.below(symbols.len())])
+                        .collect();
+                    line.push('\n');
+                    line
+                })
+                .collect();
+            let arr = process_contents(&contents, &default_components()).unwrap();
+            assert_eq!(part2(&arr), Some(part2_memo(&arr) as u128), "{contents}");
+        }
+    }
 }
@@ -1,146 +1,185 @@
-use itertools::Itertools;
-use ndarray::prelude::*;
+use crate::disjoint_set::DisjointSet;
+use crate::output;
+use crate::spatial::{ClosestPairs, Point3};
 use std::cmp::min;
-use std::collections::HashSet;
+use std::collections::BTreeMap;
 
-fn process_line(line: &str) -> (u32, u32, u32) {
+fn process_line(line: &str) -> Point3 {
     let n = |x: Option<&str>| x.unwrap().parse::<u32>().unwrap();
     let mut sp = line.split(',');
     return (n(sp.next()), n(sp.next()), n(sp.next()));
 }
 
-fn process_contents(contents: &String) -> Vec<(u32, u32, u32)> {
+fn process_contents(contents: &String) -> Vec<Point3> {
     return contents.split_whitespace().map(process_line).collect();
 }
 
-fn l2_dist(x: (u32, u32, u32), y: (u32, u32, u32)) -> f64 {
-    let d = (
-        x.0.abs_diff(y.0) as u64,
-        x.1.abs_diff(y.1) as u64,
-        x.2.abs_diff(y.2) as u64,
-    );
-    let sq_dist = d.0 * d.0 + d.1 * d.1 + d.2 * d.2;
-    return f64::sqrt(sq_dist as f64);
+fn part1_new(coords: &Vec<Point3>, n_loop: usize, n_prod: usize) -> u64 {
+    let mut circuits = DisjointSet::new(coords.len());
+    for (i, j, _) in ClosestPairs::new(coords).take(n_loop) {
+        circuits.union(i, j);
+    }
+    let mut lens = circuits.component_sizes();
+    lens.sort();
+    lens.reverse();
+    let n_ret = min(n_prod, lens.len());
+    return lens[..n_ret].iter().fold(1, |acc, x| acc * (*x as u64));
 }
 
-fn merge_loops(loops: &Vec<HashSet<usize>>) -> Vec<HashSet<usize>> {
-    let mut unique_loops: Vec<HashSet<usize>>;
-    let mut curr_loops = loops.clone();
-    let mut prev_len = 0;
-    loop {
-        unique_loops = Vec::new();
-        for s in curr_loops {
-            let mut is_disjoint = true;
-            for u in unique_loops.iter_mut() {
-                if !s.is_disjoint(&u) {
-                    u.extend(&s);
-                    is_disjoint = false;
-                }
-            }
-            if is_disjoint {
-                unique_loops.push(s.clone());
-            }
-        }
-        curr_loops = unique_loops.clone();
-        if unique_loops.len() == prev_len {
+/// One edge of the minimum spanning tree, in the order circuits merged.
+#[derive(Debug, Clone, PartialEq)]
+struct MergeStep {
+    i: usize,
+    j: usize,
+    sq_len: u64,
+    /// Sizes of the two circuits this merge joined
+    joined: (usize, usize),
+    /// Number of circuits left after this merge
+    num_circuits: usize,
+}
+
+impl MergeStep {
+    /// Size of the circuit formed by this merge.
+    fn merged_size(&self) -> usize {
+        return self.joined.0 + self.joined.1;
+    }
+}
+
+/// Kruskal's algorithm over the junction boxes, stopping once every box is
+/// in one circuit.
+fn spanning_tree(coords: &Vec<Point3>) -> Vec<MergeStep> {
+    let mut circuits = DisjointSet::new(coords.len());
+    let mut ret = Vec::new();
+    for (i, j, sq_len) in ClosestPairs::new(coords) {
+        if circuits.num_components() == 1 {
             break;
         }
-        prev_len = unique_loops.len();
+        let joined = (circuits.size(i), circuits.size(j));
+        if circuits.union(i, j) {
+            ret.push(MergeStep {
+                i,
+                j,
+                sq_len,
+                joined,
+                num_circuits: circuits.num_components(),
+            });
+        }
     }
-    return unique_loops;
+    return ret;
 }
 
-fn sorted_dists(coords: &Vec<(u32, u32, u32)>) -> Vec<(usize, usize)> {
-    let n = coords.len();
-    let mut ret: Vec<_> = (0..n)
-        .flat_map(|i| ((i + 1)..n).map(move |j| (i, j)))
-        .collect();
-    let cmp = |ij1: &(usize, usize), ij2: &(usize, usize)| {
-        let a1 = l2_dist(coords[ij1.0], coords[ij1.1]);
-        let a2 = l2_dist(coords[ij2.0], coords[ij2.1]);
-        return a1.total_cmp(&a2);
-    };
-    ret.sort_by(cmp);
+fn spanning_tree_dot(coords: &Vec<Point3>, tree: &Vec<MergeStep>) -> String {
+    let mut ret = String::from("graph junctions {\n");
+    for (idx, c) in coords.iter().enumerate() {
+        ret += &format!("  {idx} [label=\"{},{},{}\"];\n", c.0, c.1, c.2);
+    }
+    for (step, m) in tree.iter().enumerate() {
+        ret += &format!("  {} -- {} [label=\"{step}: {}\"];\n", m.i, m.j, m.sq_len);
+    }
+    ret += "}\n";
     return ret;
 }
 
-fn create_loops(queue: &[(usize, usize)]) -> Vec<HashSet<usize>> {
-    let mut ret: Vec<HashSet<usize>> = Vec::default();
-    for cart_idx in queue {
-        let (row, col) = cart_idx;
-        let mut is_used = false;
-        for s in &mut ret {
-            if s.contains(row) {
-                s.insert(*col);
-                is_used = true;
+/// How many circuits there are of each size after every step, replayed from
+/// the sizes each merge joined.
+fn cluster_sizes(num_boxes: usize, tree: &Vec<MergeStep>) -> Vec<BTreeMap<usize, usize>> {
+    let mut counts = BTreeMap::from([(1, num_boxes)]);
+    let mut ret = Vec::with_capacity(tree.len());
+    for m in tree {
+        for size in [m.joined.0, m.joined.1] {
+            let count = counts
+                .get_mut(&size)
+                .expect("Merged a circuit that does not exist");
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&size);
             }
-            if s.contains(col) {
-                s.insert(*row);
-                is_used = true;
-            }
-        }
-        if !is_used {
-            let mut s = HashSet::new();
-            s.insert(*row);
-            s.insert(*col);
-            ret.push(s);
         }
+        *counts.entry(m.merged_size()).or_default() += 1;
+        ret.push(counts.clone());
     }
     return ret;
 }
 
-fn part1_new(coords: &Vec<(u32, u32, u32)>, n_loop: usize, n_prod: usize) -> u64 {
-    let dists = sorted_dists(coords);
-    let n_max = min(dists.len(), n_loop);
-    let loops = create_loops(&dists[..n_max]);
-    let loops_merged = merge_loops(&loops);
-    let mut lens: Vec<_> = loops_merged.iter().map(|s| s.len()).collect();
-    lens.sort();
-    lens.reverse();
-    let n_ret = min(n_prod, lens.len());
-    return lens[..n_ret].iter().fold(1, |acc, x| acc * (*x as u64));
+/// One row per merge. `cluster_sizes` lists `size:count` pairs, largest
+/// circuits first.
+fn spanning_tree_csv(num_boxes: usize, tree: &Vec<MergeStep>) -> String {
+    let mut ret = String::from("step,i,j,sq_len,merged_size,num_circuits,cluster_sizes\n");
+    for (step, (m, sizes)) in tree.iter().zip(cluster_sizes(num_boxes, tree)).enumerate() {
+        let sizes: Vec<_> = sizes
+            .iter()
+            .rev()
+            .map(|(s, c)| format!("{s}:{c}"))
+            .collect();
+        ret += &format!(
+            "{step},{},{},{},{},{},{}\n",
+            m.i,
+            m.j,
+            m.sq_len,
+            m.merged_size(),
+            m.num_circuits,
+            sizes.join(" ")
+        );
+    }
+    return ret;
 }
 
-fn part2(coords: &Vec<(u32, u32, u32)>) -> u64 {
-    let mut dists = sorted_dists(coords);
-    dists.reverse();
-    let mut assignments: Array1<i64> = Array1::from_elem((coords.len(),), -1i64);
-    let mut next_connect;
-    let mut next_junction_loop = 0;
-    loop {
-        next_connect = dists.pop().unwrap();
-        let r_1 = assignments[next_connect.0];
-        let r_2 = assignments[next_connect.1];
-        if r_1 < 0 && r_2 < 0 {
-            assignments[next_connect.0] = next_junction_loop;
-            assignments[next_connect.1] = next_junction_loop;
-            next_junction_loop += 1;
-        } else if r_1 < 0 {
-            assignments[next_connect.0] = assignments[next_connect.1];
-        } else if r_2 < 0 {
-            assignments[next_connect.1] = assignments[next_connect.0];
-        } else if r_1 != r_2 {
-            let mod_assgn = assignments[next_connect.0];
-            let to_assgn = assignments[next_connect.1];
-            for idx in 0..assignments.len() {
-                if assignments[idx] == mod_assgn {
-                    assignments[idx] = to_assgn;
-                }
-            }
-        }
-        let (&a_min, &a_max) = assignments.iter().minmax().into_option().unwrap();
-        if a_min == a_max {
-            break;
-        }
+fn part2(coords: &Vec<Point3>, tree: &Vec<MergeStep>) -> Option<u64> {
+    let last = tree.last()?;
+    if last.num_circuits != 1 {
+        return None;
     }
-    let (c1, c2) = (coords[next_connect.0], coords[next_connect.1]);
-    return (c1.0 as u64) * (c2.0 as u64);
+    let (c1, c2) = (coords[last.i], coords[last.j]);
+    return Some((c1.0 as u64) * (c2.0 as u64));
 }
 
 pub fn day8(contents: &String) {
     let coords = process_contents(contents);
     let p1 = part1_new(&coords, 1000, 3);
-    println!("Part 1: {p1}");
-    let p2 = part2(&coords);
-    println!("Part 2: {p2}");
+    output::answer(1, p1);
+    let tree = spanning_tree(&coords);
+    let p2 = part2(&coords, &tree).unwrap();
+    output::answer(2, p2);
+    output::export("day8_mst.dot", || spanning_tree_dot(&coords, &tree));
+    output::export("day8_mst.csv", || spanning_tree_csv(coords.len(), &tree));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rng::Rng;
+
+    const EXAMPLE: &str = "\
+162,817,812 57,618,57 906,360,560 592,479,940 352,342,300 466,668,158 542,29,236
+431,825,988 739,650,466 52,470,668 216,146,977 819,987,18 117,168,530 805,96,715
+346,949,466 970,615,88 941,993,340 862,61,35 984,92,344 425,690,689
+";
+
+    #[test]
+    fn example_answers() {
+        let coords = process_contents(&String::from(EXAMPLE));
+        assert_eq!(part1_new(&coords, 10, 3), 40);
+        assert_eq!(part2(&coords, &spanning_tree(&coords)), Some(25272));
+    }
+
+    #[test]
+    fn cluster_sizes_match_replayed_merges() {
+        let mut rng = Rng::new(8);
+        let coords: Vec<Point3> = (0..60)
+            .map(|_| {
+                let mut c = || rng.below(1000) as u32;
+                (c(), c(), c())
+            })
+            .collect();
+        let tree = spanning_tree(&coords);
+        let mut circuits = DisjointSet::new(coords.len());
+        for (m, sizes) in tree.iter().zip(cluster_sizes(coords.len(), &tree)) {
+            circuits.union(m.i, m.j);
+            let mut expected: BTreeMap<usize, usize> = BTreeMap::new();
+            for size in circuits.component_sizes() {
+                *expected.entry(size).or_default() += 1;
+            }
+            assert_eq!(sizes, expected);
+        }
+    }
 }
@@ -1,34 +1,44 @@
+use itertools::Itertools;
+
+#[cfg(feature = "geo")]
+use crate::logging::warning;
+use crate::output;
+use crate::progress::ReportProgress;
+use crate::rectilinear::{CompressedGrid, Point2};
+#[cfg(feature = "geo")]
 use geo::{coord, point, Contains, LineString, Polygon, Rect};
 
-fn process_line(line: &str) -> (u64, u64) {
+fn process_line(line: &str) -> Point2 {
     let n = |x: Option<&str>| x.unwrap().parse::<u64>().unwrap();
     let mut sp = line.split(',');
     return (n(sp.next()), n(sp.next()));
 }
 
-fn process_contents(contents: &String) -> Vec<(u64, u64)> {
+fn process_contents(contents: &String) -> Vec<Point2> {
     return contents.split_whitespace().map(process_line).collect();
 }
 
-fn l2_dist(x: (u64, u64), y: (u64, u64)) -> u64 {
+fn l2_dist(x: Point2, y: Point2) -> u64 {
     let d = (x.0.abs_diff(y.0) as u64 + 1, x.1.abs_diff(y.1) as u64 + 1);
     return d.0 * d.1;
 }
 
-fn part1(coords: &Vec<(u64, u64)>) -> u64 {
-    let mut ret = 0;
+/// Returns the largest area and the indices of its corner tiles.
+fn part1(coords: &Vec<Point2>) -> (u64, (usize, usize)) {
+    let mut ret = (0, (0, 0));
     for i in 0..coords.len() {
         for j in (i + 1)..coords.len() {
             let d = l2_dist(coords[i], coords[j]);
-            if d > ret {
-                ret = d;
+            if d > ret.0 {
+                ret = (d, (i, j));
             }
         }
     }
     return ret;
 }
 
-fn part2_geom(coords: &Vec<(u64, u64)>) -> u64 {
+#[cfg(feature = "geo")]
+fn part2_geom(coords: &Vec<Point2>) -> u64 {
     let points: Vec<_> = coords
         .iter()
         .map(|c| {
@@ -60,10 +70,148 @@ fn part2_geom(coords: &Vec<(u64, u64)>) -> u64 {
     return max;
 }
 
+/// Returns the largest area inside the loop and the indices of its corner
+/// tiles.
+fn part2(coords: &Vec<Point2>) -> Option<(u64, (usize, usize))> {
+    let grid = CompressedGrid::new(coords)?;
+    let mut max = (0, (0, 0));
+    for i in (0..coords.len()).report("Day 9 part 2") {
+        for j in (i + 1)..coords.len() {
+            let d_ij = l2_dist(coords[i], coords[j]);
+            if d_ij > max.0 && grid.contains_rect(coords[i], coords[j])? {
+                max = (d_ij, (i, j));
+            }
+        }
+    }
+    return Some(max);
+}
+
+/// Draws the red-tile loop filled green, with the part 1 rectangle in blue
+/// and the part 2 rectangle in gold. Each tile is a unit square.
+fn render_svg(coords: &Vec<Point2>, best_p1: (usize, usize), best_p2: (usize, usize)) -> String {
+    let x_min = coords.iter().map(|c| c.0).min().unwrap_or(0);
+    let y_min = coords.iter().map(|c| c.1).min().unwrap_or(0);
+    let width = coords.iter().map(|c| c.0 - x_min).max().unwrap_or(0) + 1;
+    let height = coords.iter().map(|c| c.1 - y_min).max().unwrap_or(0) + 1;
+    let font_size = (width.max(height) as f64 / 60.).max(1.);
+    let mut ret = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{x_min} {y_min} {width} {height}\">\n"
+    );
+    let points = coords
+        .iter()
+        .map(|c| format!("{}.5,{}.5", c.0, c.1))
+        .join(" ");
+    ret += &format!(
+        "  <polygon points=\"{points}\" fill=\"#8fd18f\" stroke=\"red\" \
+         stroke-width=\"2\" vector-effect=\"non-scaling-stroke\"/>\n"
+    );
+    for c in coords {
+        ret += &format!(
+            "  <rect x=\"{}\" y=\"{}\" width=\"1\" height=\"1\" fill=\"red\"/>\n",
+            c.0, c.1
+        );
+    }
+    for (label, color, (i, j)) in [("part 1", "blue", best_p1), ("part 2", "gold", best_p2)] {
+        let (c_i, c_j) = (coords[i], coords[j]);
+        let (x, y) = (c_i.0.min(c_j.0), c_i.1.min(c_j.1));
+        let (w, h) = (c_i.0.abs_diff(c_j.0) + 1, c_i.1.abs_diff(c_j.1) + 1);
+        ret += &format!(
+            "  <rect x=\"{x}\" y=\"{y}\" width=\"{w}\" height=\"{h}\" fill=\"none\" \
+             stroke=\"{color}\" stroke-width=\"3\" vector-effect=\"non-scaling-stroke\"/>\n"
+        );
+        for (idx, c) in [(i, c_i), (j, c_j)] {
+            ret += &format!(
+                "  <text x=\"{}\" y=\"{}\" font-size=\"{font_size}\" fill=\"{color}\">{label}: {idx}</text>\n",
+                c.0, c.1
+            );
+        }
+    }
+    ret += "</svg>\n";
+    return ret;
+}
+
 pub fn day9(contents: &String) {
     let coords = process_contents(contents);
-    let p1 = part1(&coords);
-    println!("Part 1: {p1}");
-    let p2 = part2_geom(&coords);
-    println!("Part 2: {p2}");
+    let (p1, best_p1) = part1(&coords);
+    output::answer(1, p1);
+    let (p2, best_p2) = part2(&coords).expect("Red tiles do not form a rectilinear loop");
+    #[cfg(feature = "geo")]
+    {
+        let p2_geom = part2_geom(&coords);
+        if p2_geom != p2 {
+            warning!("geo cross-check gives {p2_geom}, not {p2}; f32 loses precision past 2^24");
+        }
+    }
+    output::answer(2, p2);
+    output::export("day9.svg", || render_svg(&coords, best_p1, best_p2));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rectilinear::RectilinearPolygon;
+    use crate::test_rng::Rng;
+
+    fn part2_polygon(coords: &Vec<Point2>) -> Option<u64> {
+        let poly = RectilinearPolygon::new(coords)?;
+        let mut max = 0;
+        for i in 0..coords.len() {
+            for j in (i + 1)..coords.len() {
+                let d_ij = l2_dist(coords[i], coords[j]);
+                if d_ij > max && poly.contains_rect(coords[i], coords[j]) {
+                    max = d_ij;
+                }
+            }
+        }
+        return Some(max);
+    }
+
+    const EXAMPLE: &str = "7,1 11,1 11,7 9,7 9,5 2,5 2,3 7,3";
+
+    #[test]
+    fn part2_matches_polygon_on_example() {
+        let coords = process_contents(&String::from(EXAMPLE));
+        assert_eq!(part2(&coords).map(|p| p.0), Some(24));
+        assert_eq!(part2_polygon(&coords), Some(24));
+    }
+
+    /// Skyline loops: random column tops walked left to right, then random
+    /// column bottoms walked back. Tops and bottoms never meet, so the loop
+    /// is simple.
+    #[test]
+    fn part2_matches_polygon_on_random_loops() {
+        let mut rng = Rng::new(9);
+        for _ in 0..200 {
+            let num_columns = 1 + rng.below(8);
+            let mut xs = vec![rng.below(5) as u64];
+            for _ in 0..num_columns {
+                xs.push(xs.last().unwrap() + 1 + rng.below(4) as u64);
+            }
+            let mut heights = |lo: u64| {
+                let mut ret: Vec<u64> = Vec::new();
+                while ret.len() < num_columns {
+                    let h = lo + rng.below(10) as u64;
+                    if ret.last() != Some(&h) {
+                        ret.push(h);
+                    }
+                }
+                return ret;
+            };
+            let (tops, bottoms) = (heights(10), heights(0));
+            let mut coords = Vec::new();
+            for i in 0..num_columns {
+                coords.push((xs[i], tops[i]));
+                coords.push((xs[i + 1], tops[i]));
+            }
+            for i in (0..num_columns).rev() {
+                coords.push((xs[i + 1], bottoms[i]));
+                coords.push((xs[i], bottoms[i]));
+            }
+            assert_eq!(
+                part2(&coords).map(|p| p.0),
+                part2_polygon(&coords),
+                "{coords:?}"
+            );
+        }
+    }
 }
@@ -0,0 +1,72 @@
+/// Union-find over `0..n` with union by rank and path compression.
+#[derive(Debug, Clone)]
+pub struct DisjointSet {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+    size: Vec<usize>,
+    num_components: usize,
+}
+
+impl DisjointSet {
+    pub fn new(n: usize) -> DisjointSet {
+        return DisjointSet {
+            parent: (0..n).collect(),
+            rank: vec![0; n],
+            size: vec![1; n],
+            num_components: n,
+        };
+    }
+
+    /// Returns the representative of the set containing `x`.
+    pub fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut curr = x;
+        while self.parent[curr] != root {
+            let next = self.parent[curr];
+            self.parent[curr] = root;
+            curr = next;
+        }
+        return root;
+    }
+
+    /// Merges the sets containing `a` and `b`. Returns `false` if they were
+    /// already in the same set.
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut root_a, mut root_b) = (self.find(a), self.find(b));
+        if root_a == root_b {
+            return false;
+        }
+        if self.rank[root_a] < self.rank[root_b] {
+            (root_a, root_b) = (root_b, root_a);
+        }
+        self.parent[root_b] = root_a;
+        self.size[root_a] += self.size[root_b];
+        if self.rank[root_a] == self.rank[root_b] {
+            self.rank[root_a] += 1;
+        }
+        self.num_components -= 1;
+        return true;
+    }
+
+    /// Size of the set containing `x`.
+    pub fn size(&mut self, x: usize) -> usize {
+        let root = self.find(x);
+        return self.size[root];
+    }
+
+    pub fn num_components(&self) -> usize {
+        return self.num_components;
+    }
+
+    /// Sizes of every set, one entry per component.
+    pub fn component_sizes(&self) -> Vec<usize> {
+        let n = self.parent.len();
+        return (0..n)
+            .filter(|&x| self.parent[x] == x)
+            .map(|x| self.size[x])
+            .collect();
+    }
+}
@@ -0,0 +1,282 @@
+use crate::budget::Meter;
+use std::collections::VecDeque;
+
+const WORD: usize = u64::BITS as usize;
+
+fn get_bit(row: &[u64], idx: usize) -> bool {
+    return (row[idx / WORD] >> (idx % WORD)) & 1 == 1;
+}
+
+fn xor_into(dst: &mut [u64], src: &[u64]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d ^= s;
+    }
+}
+
+fn count_ones(row: &[u64]) -> usize {
+    return row.iter().map(|w| w.count_ones() as usize).sum();
+}
+
+fn pack(bits: impl Iterator<Item = bool>, len: usize) -> Vec<u64> {
+    let mut ret = vec![0; len.div_ceil(WORD)];
+    for (idx, b) in bits.enumerate() {
+        ret[idx / WORD] |= (b as u64) << (idx % WORD);
+    }
+    return ret;
+}
+
+/// Largest number of free variables walked exhaustively, and largest rank
+/// whose `2^rank` pivot values are tabulated.
+const EXHAUSTIVE_MAX: usize = 20;
+
+/// Bit-packed linear system over GF(2) after Gauss-Jordan elimination. The
+/// pivot variable of row `r` is bit `r` of `rhs`, flipped by every chosen
+/// free variable `f` whose `free_vecs[f]` has bit `r` set.
+struct Reduced {
+    pivot_cols: Vec<usize>,
+    free_cols: Vec<usize>,
+    free_vecs: Vec<Vec<u64>>,
+    rhs: Vec<u64>,
+}
+
+/// `columns[j]` lists the rows where column `j` has a one. Returns `None` if
+/// `A x = target` has no solution.
+fn reduce(columns: &[Vec<usize>], target: &[bool]) -> Option<Reduced> {
+    let (m, n) = (target.len(), columns.len());
+    let words = n.div_ceil(WORD);
+    let mut rows: Vec<Vec<u64>> = vec![vec![0; words]; m];
+    for (col, col_rows) in columns.iter().enumerate() {
+        for &row in col_rows {
+            rows[row][col / WORD] ^= 1 << (col % WORD);
+        }
+    }
+    let mut rhs: Vec<bool> = target.to_vec();
+    let mut pivot_cols = Vec::new();
+    let mut free_cols = Vec::new();
+    for col in 0..n {
+        let rank = pivot_cols.len();
+        let Some(pivot_row) = (rank..m).find(|&r| get_bit(&rows[r], col)) else {
+            free_cols.push(col);
+            continue;
+        };
+        rows.swap(rank, pivot_row);
+        rhs.swap(rank, pivot_row);
+        let pivot = rows[rank].clone();
+        for r in 0..m {
+            if r != rank && get_bit(&rows[r], col) {
+                xor_into(&mut rows[r], &pivot);
+                rhs[r] ^= rhs[rank];
+            }
+        }
+        pivot_cols.push(col);
+    }
+    let rank = pivot_cols.len();
+    if rhs[rank..].iter().any(|&b| b) {
+        return None;
+    }
+    let free_vecs = free_cols
+        .iter()
+        .map(|&f| pack(rows[..rank].iter().map(|row| get_bit(row, f)), rank))
+        .collect();
+    let rhs = pack(rhs[..rank].iter().copied(), rank);
+    return Some(Reduced {
+        pivot_cols,
+        free_cols,
+        free_vecs,
+        rhs,
+    });
+}
+
+/// Walks every subset of at most `EXHAUSTIVE_MAX` free variables in Gray code
+/// order, returning the lightest one as a bit mask, or `None` if `meter`
+/// runs out.
+fn search_gray_code(reduced: &Reduced, meter: &mut Meter) -> Option<u64> {
+    let k = reduced.free_cols.len();
+    let (mut subset, mut pivots) = (0u64, reduced.rhs.clone());
+    let mut best = (count_ones(&pivots), 0u64);
+    for step in 1..(1u64 << k) {
+        if !meter.tick() {
+            return None;
+        }
+        let flip = step.trailing_zeros() as usize;
+        subset ^= 1 << flip;
+        xor_into(&mut pivots, &reduced.free_vecs[flip]);
+        let weight = subset.count_ones() as usize + count_ones(&pivots);
+        if weight < best.0 {
+            best = (weight, subset);
+        }
+    }
+    return Some(best.1);
+}
+
+/// Breadth-first search over the `2^rank` values the free variables can give
+/// the pivots, for a rank of at most `EXHAUSTIVE_MAX`, finding the fewest
+/// free variables reaching each. Returns the lightest subset as a list of
+/// free variable indices, or `None` if `meter` runs out.
+fn search_pivot_space(reduced: &Reduced, meter: &mut Meter) -> Option<Vec<usize>> {
+    let rank = reduced.pivot_cols.len();
+    // A single word holds every pivot, or none at all for rank zero
+    let word = |v: &Vec<u64>| v.first().copied().unwrap_or(0) as usize;
+    let free_vecs: Vec<usize> = reduced.free_vecs.iter().map(word).collect();
+    let rhs = word(&reduced.rhs);
+    let mut dist: Vec<u32> = vec![u32::MAX; 1 << rank];
+    let mut parent: Vec<usize> = vec![0; 1 << rank];
+    let mut queue = VecDeque::from([0usize]);
+    dist[0] = 0;
+    while let Some(state) = queue.pop_front() {
+        if !meter.tick() {
+            return None;
+        }
+        for (f, &v) in free_vecs.iter().enumerate() {
+            let next = state ^ v;
+            if dist[next] == u32::MAX {
+                dist[next] = dist[state] + 1;
+                parent[next] = f;
+                queue.push_back(next);
+            }
+        }
+    }
+    let weight = |s: usize| dist[s].saturating_add((s ^ rhs).count_ones());
+    let mut state = (0..dist.len()).min_by_key(|&s| weight(s)).unwrap_or(0);
+    let mut ret = Vec::new();
+    while state != 0 {
+        let f = parent[state];
+        ret.push(f);
+        state ^= free_vecs[f];
+    }
+    return Some(ret);
+}
+
+/// Tries subsets of free variables one size at a time, smallest first. A
+/// subset of size `s` weighs at least `s`, so sizes stop once they reach the
+/// best weight found.
+struct SizeSearch<'a> {
+    reduced: &'a Reduced,
+    meter: &'a mut Meter,
+    chosen: Vec<usize>,
+    pivots: Vec<u64>,
+    best: (usize, Vec<usize>),
+}
+
+impl SizeSearch<'_> {
+    /// Extends `chosen` to `size` variables from `from` onwards. Returns
+    /// false once the meter runs out.
+    fn extend(&mut self, size: usize, from: usize) -> bool {
+        if self.best.0 <= size {
+            return true;
+        }
+        if self.chosen.len() == size {
+            let weight = size + count_ones(&self.pivots);
+            if weight < self.best.0 {
+                self.best = (weight, self.chosen.clone());
+            }
+            return true;
+        }
+        let last = self.reduced.free_cols.len() - (size - self.chosen.len());
+        for f in from..=last {
+            if !self.meter.tick() {
+                return false;
+            }
+            let v = &self.reduced.free_vecs[f];
+            self.chosen.push(f);
+            xor_into(&mut self.pivots, v);
+            let finished = self.extend(size, f + 1);
+            self.chosen.pop();
+            xor_into(&mut self.pivots, v);
+            if !finished {
+                return false;
+            }
+        }
+        return true;
+    }
+}
+
+/// Lightest subset of free variables by increasing subset size, as a list of
+/// free variable indices, or `None` if `meter` runs out.
+fn search_by_size(reduced: &Reduced, meter: &mut Meter) -> Option<Vec<usize>> {
+    let mut search = SizeSearch {
+        reduced,
+        meter,
+        chosen: Vec::new(),
+        pivots: reduced.rhs.clone(),
+        best: (count_ones(&reduced.rhs), Vec::new()),
+    };
+    for size in 1..=reduced.free_cols.len() {
+        if !search.extend(size, 0) {
+            return None;
+        }
+    }
+    return Some(search.best.1);
+}
+
+/// Minimum-weight `x` with `A x = target` over GF(2), where `columns[j]`
+/// lists the rows in which column `j` has a one. Walks the null space or
+/// the pivot space exhaustively when either is small, and otherwise tries
+/// subsets of free variables by increasing size. Returns `None` if there is
+/// no solution or `meter` runs out first.
+pub fn min_weight_solution(
+    columns: &[Vec<usize>],
+    target: &[bool],
+    meter: &mut Meter,
+) -> Option<Vec<bool>> {
+    let reduced = reduce(columns, target)?;
+    let (rank, k) = (reduced.pivot_cols.len(), reduced.free_cols.len());
+    let chosen: Vec<usize> = if k <= EXHAUSTIVE_MAX && k <= rank {
+        let subset = search_gray_code(&reduced, meter)?;
+        (0..k).filter(|&f| (subset >> f) & 1 == 1).collect()
+    } else if rank <= EXHAUSTIVE_MAX {
+        search_pivot_space(&reduced, meter)?
+    } else {
+        search_by_size(&reduced, meter)?
+    };
+    let mut x = vec![false; columns.len()];
+    let mut pivots = reduced.rhs;
+    for f in chosen {
+        x[reduced.free_cols[f]] = true;
+        xor_into(&mut pivots, &reduced.free_vecs[f]);
+    }
+    for (r, &p) in reduced.pivot_cols.iter().enumerate() {
+        x[p] = get_bit(&pivots, r);
+    }
+    return Some(x);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::Budget;
+    use crate::test_rng::Rng;
+
+    /// Weight of `x` once the chosen free variables fix the pivots.
+    fn weight(reduced: &Reduced, chosen: &[usize]) -> usize {
+        let mut pivots = reduced.rhs.clone();
+        for &f in chosen {
+            xor_into(&mut pivots, &reduced.free_vecs[f]);
+        }
+        return chosen.len() + count_ones(&pivots);
+    }
+
+    #[test]
+    fn searches_agree_on_random_systems() {
+        let mut rng = Rng::new(2);
+        let budget = Budget::default();
+        for _ in 0..300 {
+            let (m, n) = (1 + rng.below(10), 1 + rng.below(14));
+            let columns: Vec<Vec<usize>> = (0..n)
+                .map(|_| (0..m).filter(|_| rng.below(2) == 0).collect())
+                .collect();
+            let target: Vec<bool> = (0..m).map(|_| rng.below(2) == 1).collect();
+            let Some(reduced) = reduce(&columns, &target) else {
+                continue;
+            };
+            let k = reduced.free_cols.len();
+            let gray = search_gray_code(&reduced, &mut budget.start()).unwrap();
+            let gray: Vec<usize> = (0..k).filter(|&f| (gray >> f) & 1 == 1).collect();
+            let pivot_space = search_pivot_space(&reduced, &mut budget.start()).unwrap();
+            let by_size = search_by_size(&reduced, &mut budget.start()).unwrap();
+            let expected = weight(&reduced, &gray);
+            assert_eq!(weight(&reduced, &pivot_space), expected);
+            assert_eq!(weight(&reduced, &by_size), expected);
+        }
+    }
+}
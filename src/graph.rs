@@ -0,0 +1,272 @@
+use std::collections::VecDeque;
+
+use itertools::Itertools;
+
+/// Directed graph over nodes `0..n` stored as adjacency lists.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Graph {
+    adjacency: Vec<Vec<usize>>,
+}
+
+impl Graph {
+    pub fn new(num_nodes: usize) -> Graph {
+        return Graph {
+            adjacency: vec![Vec::new(); num_nodes],
+        };
+    }
+
+    pub fn add_edge(&mut self, from: usize, to: usize) {
+        self.adjacency[from].push(to);
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        return self.adjacency.len();
+    }
+
+    pub fn neighbors(&self, node: usize) -> &[usize] {
+        return &self.adjacency[node];
+    }
+
+    /// Kahn's algorithm. On failure returns the nodes of one cycle, in order.
+    pub fn topological_order(&self) -> Result<Vec<usize>, Vec<usize>> {
+        let n = self.num_nodes();
+        let mut in_degree = vec![0usize; n];
+        for &to in self.adjacency.iter().flatten() {
+            in_degree[to] += 1;
+        }
+        let mut queue: VecDeque<usize> = (0..n).filter(|&v| in_degree[v] == 0).collect();
+        let mut order = Vec::with_capacity(n);
+        while let Some(v) = queue.pop_front() {
+            order.push(v);
+            for &w in &self.adjacency[v] {
+                in_degree[w] -= 1;
+                if in_degree[w] == 0 {
+                    queue.push_back(w);
+                }
+            }
+        }
+        if order.len() == n {
+            return Ok(order);
+        }
+        return Err(self.find_cycle(&in_degree));
+    }
+
+    /// Every node left with a positive in-degree after Kahn's algorithm has a
+    /// predecessor that is also left, so walking backwards must loop.
+    fn find_cycle(&self, in_degree: &[usize]) -> Vec<usize> {
+        let n = self.num_nodes();
+        let mut predecessor = vec![usize::MAX; n];
+        for v in (0..n).filter(|&v| in_degree[v] > 0) {
+            for &w in &self.adjacency[v] {
+                if in_degree[w] > 0 {
+                    predecessor[w] = v;
+                }
+            }
+        }
+        let mut seen = vec![false; n];
+        let mut curr = (0..n).find(|&v| in_degree[v] > 0).unwrap();
+        while !seen[curr] {
+            seen[curr] = true;
+            curr = predecessor[curr];
+        }
+        let mut cycle = vec![curr];
+        let mut prev = predecessor[curr];
+        while prev != curr {
+            cycle.push(prev);
+            prev = predecessor[prev];
+        }
+        cycle.reverse();
+        return cycle;
+    }
+
+    /// Checks the graph is acyclic, returning one cycle if not.
+    pub fn into_dag(self) -> Result<Dag, Vec<usize>> {
+        let order = self.topological_order()?;
+        return Ok(Dag { graph: self, order });
+    }
+}
+
+/// Acyclic graph together with a topological order of its nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dag {
+    graph: Graph,
+    order: Vec<usize>,
+}
+
+/// Paths from `start` to `end` that visit every node in `required` and never
+/// touch a node in `forbidden`. The required nodes are met in the order
+/// listed if `ordered` is set, else in any order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PathQuery {
+    pub start: usize,
+    pub end: usize,
+    pub required: Vec<usize>,
+    pub forbidden: Vec<usize>,
+    pub ordered: bool,
+}
+
+impl Dag {
+    /// Number of paths from `from` to `to` not passing through any `blocked`
+    /// node, memoized over the topological order. Returns `None` on overflow.
+    fn count_paths_avoiding(&self, from: usize, to: usize, blocked: &[bool]) -> Option<u64> {
+        let mut counts = vec![0u64; self.graph.num_nodes()];
+        if blocked[to] {
+            return Some(0);
+        }
+        counts[to] = 1;
+        for &v in self.order.iter().rev() {
+            if v == to || blocked[v] {
+                continue;
+            }
+            let mut count = 0u64;
+            for &w in self.graph.neighbors(v) {
+                count = count.checked_add(counts[w])?;
+            }
+            counts[v] = count;
+        }
+        return Some(counts[from]);
+    }
+
+    /// Number of paths from `from` to `to`. Returns `None` on overflow.
+    pub fn count_paths(&self, from: usize, to: usize) -> Option<u64> {
+        let blocked = vec![false; self.graph.num_nodes()];
+        return self.count_paths_avoiding(from, to, &blocked);
+    }
+
+    /// Number of paths from `from` to `to` visiting `waypoints` in the given
+    /// order and avoiding `blocked` nodes. Returns `None` on overflow.
+    fn count_paths_ordered(
+        &self,
+        from: usize,
+        waypoints: &[usize],
+        to: usize,
+        blocked: &[bool],
+    ) -> Option<u64> {
+        let stops: Vec<usize> = [from]
+            .iter()
+            .chain(waypoints)
+            .chain([to].iter())
+            .copied()
+            .collect();
+        let mut ret = 1u64;
+        for leg in stops.windows(2) {
+            ret = ret.checked_mul(self.count_paths_avoiding(leg[0], leg[1], blocked)?)?;
+        }
+        return Some(ret);
+    }
+
+    /// Answers a `PathQuery`. Every path meets nodes in topological order, so
+    /// unordered required nodes can only be visited in that order. Returns
+    /// `None` on overflow.
+    pub fn count_query(&self, query: &PathQuery) -> Option<u64> {
+        let n = self.graph.num_nodes();
+        let mut position = vec![0; n];
+        for (pos, &v) in self.order.iter().enumerate() {
+            position[v] = pos;
+        }
+        let mut blocked = vec![false; n];
+        for &v in &query.forbidden {
+            blocked[v] = true;
+        }
+        if blocked[query.start] || query.required.iter().any(|&v| blocked[v]) {
+            return Some(0);
+        }
+        let waypoints: Vec<usize> = if query.ordered {
+            query.required.clone()
+        } else {
+            query
+                .required
+                .iter()
+                .copied()
+                .sorted_by_key(|&v| position[v])
+                .dedup()
+                .collect()
+        };
+        return self.count_paths_ordered(query.start, &waypoints, query.end, &blocked);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rng::Rng;
+
+    /// 0 -> 1 -> 2 -> 4 with shortcuts 0 -> 2, 1 -> 3 and 3 -> 4.
+    fn example() -> Dag {
+        let mut graph = Graph::new(5);
+        for (from, to) in [(0, 1), (1, 2), (2, 4), (0, 2), (1, 3), (3, 4)] {
+            graph.add_edge(from, to);
+        }
+        return graph.into_dag().unwrap();
+    }
+
+    #[test]
+    fn ordered_waypoints_follow_the_given_order() {
+        let dag = example();
+        let query = |required: Vec<usize>, ordered: bool| PathQuery {
+            start: 0,
+            end: 4,
+            required,
+            ordered,
+            ..PathQuery::default()
+        };
+        assert_eq!(dag.count_paths(0, 4), Some(3));
+        assert_eq!(dag.count_query(&query(vec![2, 1], false)), Some(1));
+        assert_eq!(dag.count_query(&query(vec![1, 2], true)), Some(1));
+        assert_eq!(dag.count_query(&query(vec![2, 1], true)), Some(0));
+    }
+
+    /// Random edges between nodes `0..n`, optionally only from lower to
+    /// higher ids so the graph is acyclic.
+    fn random_graph(rng: &mut Rng, n: usize, num_edges: usize, acyclic: bool) -> Graph {
+        let mut graph = Graph::new(n);
+        for _ in 0..num_edges {
+            let (a, b) = (rng.below(n), rng.below(n));
+            if acyclic && a == b {
+                continue;
+            }
+            let (from, to) = if acyclic {
+                (a.min(b), a.max(b))
+            } else {
+                (a, b)
+            };
+            graph.add_edge(from, to);
+        }
+        return graph;
+    }
+
+    #[test]
+    fn order_respects_edges_and_cycles_are_edges() {
+        let mut rng = Rng::new(38);
+        let (mut orders, mut cycles) = (0, 0);
+        for trial in 0..500 {
+            let n = 1 + rng.below(12);
+            let num_edges = rng.below(2 * n);
+            let graph = random_graph(&mut rng, n, num_edges, trial % 2 == 0);
+            match graph.topological_order() {
+                Ok(order) => {
+                    orders += 1;
+                    let mut position = vec![usize::MAX; n];
+                    for (pos, &v) in order.iter().enumerate() {
+                        position[v] = pos;
+                    }
+                    assert!(position.iter().all(|&pos| pos < n));
+                    for v in 0..n {
+                        for &w in graph.neighbors(v) {
+                            assert!(position[v] < position[w], "edge {v} -> {w}");
+                        }
+                    }
+                }
+                Err(cycle) => {
+                    cycles += 1;
+                    assert!(trial % 2 == 1 && !cycle.is_empty());
+                    assert!(cycle.iter().all_unique());
+                    for (&v, &w) in cycle.iter().circular_tuple_windows() {
+                        assert!(graph.neighbors(v).contains(&w), "no edge {v} -> {w}");
+                    }
+                }
+            }
+        }
+        assert!(orders > 250 && cycles > 50);
+    }
+}
@@ -0,0 +1,161 @@
+use ndarray::prelude::*;
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    return a;
+}
+
+/// Integer system `A x = b` in reduced row echelon form, kept fraction-free:
+/// each pivot row reads `pivot * x[pivot_col] + sum(row[f] * x[f]) = rhs`
+/// over the free columns `f`.
+struct Reduced {
+    rows: Array2<i128>,
+    rhs: Vec<i128>,
+    pivot_cols: Vec<usize>,
+    free_cols: Vec<usize>,
+}
+
+/// Gaussian elimination over the rationals using integer row operations.
+/// Returns `None` if the system is inconsistent.
+fn reduce(a: ArrayView2<i64>, b: &[i64]) -> Option<Reduced> {
+    let (m, n) = a.dim();
+    let mut rows: Array2<i128> = a.mapv(|x| x as i128);
+    let mut rhs: Vec<i128> = b.iter().map(|&x| x as i128).collect();
+    let mut pivot_cols = Vec::new();
+    let mut free_cols = Vec::new();
+    for col in 0..n {
+        let rank = pivot_cols.len();
+        let Some(pivot_row) = (rank..m).find(|&r| rows[[r, col]] != 0) else {
+            free_cols.push(col);
+            continue;
+        };
+        for c in 0..n {
+            rows.swap([rank, c], [pivot_row, c]);
+        }
+        rhs.swap(rank, pivot_row);
+        for r in 0..m {
+            let factor = rows[[r, col]];
+            if r == rank || factor == 0 {
+                continue;
+            }
+            let pivot = rows[[rank, col]];
+            for c in 0..n {
+                rows[[r, c]] = rows[[r, c]] * pivot - rows[[rank, c]] * factor;
+            }
+            rhs[r] = rhs[r] * pivot - rhs[rank] * factor;
+            let g = rows.row(r).iter().fold(rhs[r], |acc, &x| gcd(acc, x));
+            if g > 1 {
+                rows.row_mut(r).mapv_inplace(|x| x / g);
+                rhs[r] /= g;
+            }
+        }
+        pivot_cols.push(col);
+    }
+    if rhs[pivot_cols.len()..].iter().any(|&x| x != 0) {
+        return None;
+    }
+    return Some(Reduced {
+        rows,
+        rhs,
+        pivot_cols,
+        free_cols,
+    });
+}
+
+/// Fills in the pivot variables from the free ones, failing if any is
+/// fractional or out of bounds.
+fn back_substitute(reduced: &Reduced, x: &mut [i64], upper: &[i64]) -> bool {
+    for (r, &p) in reduced.pivot_cols.iter().enumerate() {
+        let mut num = reduced.rhs[r];
+        for &f in &reduced.free_cols {
+            num -= reduced.rows[[r, f]] * x[f] as i128;
+        }
+        let pivot = reduced.rows[[r, p]];
+        if num % pivot != 0 {
+            return false;
+        }
+        let val = num / pivot;
+        if val < 0 || val > upper[p] as i128 {
+            return false;
+        }
+        x[p] = val as i64;
+    }
+    return true;
+}
+
+fn enumerate_free(
+    reduced: &Reduced,
+    free_idx: usize,
+    x: &mut Vec<i64>,
+    free_sum: i64,
+    upper: &[i64],
+    best: &mut Option<(i64, Vec<i64>)>,
+) {
+    if best.as_ref().is_some_and(|(s, _)| free_sum >= *s) {
+        return;
+    }
+    if free_idx == reduced.free_cols.len() {
+        if back_substitute(reduced, x, upper) {
+            let total: i64 = x.iter().sum();
+            if best.as_ref().is_none_or(|(s, _)| total < *s) {
+                *best = Some((total, x.clone()));
+            }
+        }
+        return;
+    }
+    let col = reduced.free_cols[free_idx];
+    for val in 0..=upper[col] {
+        x[col] = val;
+        enumerate_free(reduced, free_idx + 1, x, free_sum + val, upper, best);
+    }
+    x[col] = 0;
+}
+
+/// Minimises `sum(x)` subject to `A x = b` with `0 <= x <= upper` integral.
+/// Eliminates down to the free variables, then enumerates those within their
+/// bounds. Returns `None` if there is no solution.
+pub fn min_sum_solution(a: ArrayView2<i64>, b: &[i64], upper: &[i64]) -> Option<Vec<i64>> {
+    let reduced = reduce(a, b)?;
+    let mut x = vec![0; a.dim().1];
+    let mut best = None;
+    enumerate_free(&reduced, 0, &mut x, 0, upper, &mut best);
+    return best.map(|(_, x)| x);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inconsistent_system_has_no_reduction() {
+        // x + y = 1 and x + y = 2
+        let a = array![[1, 1], [1, 1]];
+        assert!(reduce(a.view(), &[1, 2]).is_none());
+        assert_eq!(min_sum_solution(a.view(), &[1, 2], &[5, 5]), None);
+    }
+
+    #[test]
+    fn fractional_pivot_is_rejected() {
+        // 2 x = 3 reduces fine but has no integer solution
+        let a = array![[2]];
+        let reduced = reduce(a.view(), &[3]).unwrap();
+        assert!(!back_substitute(&reduced, &mut [0], &[5]));
+        assert_eq!(min_sum_solution(a.view(), &[3], &[5]), None);
+        // x + 2 y = 3 only works with y odd once x is bounded by 1
+        let a = array![[1, 2]];
+        assert_eq!(min_sum_solution(a.view(), &[3], &[1, 5]), Some(vec![1, 1]));
+    }
+
+    #[test]
+    fn free_variables_are_minimised() {
+        // x + z = 3, y + z = 3: pressing z alone is cheapest
+        let a = array![[1, 0, 1], [0, 1, 1]];
+        assert_eq!(
+            min_sum_solution(a.view(), &[3, 3], &[3, 3, 3]),
+            Some(vec![0, 0, 3])
+        );
+    }
+}
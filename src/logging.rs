@@ -0,0 +1,122 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+/// How much a message matters, from most to least.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    const ALL: [Level; 5] = [
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ];
+
+    fn name(self) -> &'static str {
+        return match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        };
+    }
+
+    fn from_name(name: &str) -> Option<Level> {
+        return Level::ALL.into_iter().find(|l| l.name() == name);
+    }
+}
+
+/// Most verbose level shown for each named target, and for every other one.
+struct Filter {
+    default: Level,
+    targets: Vec<(String, Level)>,
+}
+
+static FILTER: OnceLock<Filter> = OnceLock::new();
+
+fn default_filter() -> Filter {
+    return Filter {
+        default: Level::Warn,
+        targets: Vec::new(),
+    };
+}
+
+/// Reads comma-separated `target=level` entries, where a bare `level` sets
+/// the default. Only warnings and errors are shown otherwise. Only the first
+/// call has any effect.
+pub fn init(spec: &str) -> Result<(), String> {
+    let mut filter = default_filter();
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (target, name) = match entry.split_once('=') {
+            Some((target, name)) => (Some(target), name),
+            None => (None, entry),
+        };
+        let level = Level::from_name(name).ok_or_else(|| format!("Unknown log level `{name}`"))?;
+        match target {
+            Some(target) => filter.targets.push((String::from(target), level)),
+            None => filter.default = level,
+        }
+    }
+    let _ = FILTER.set(filter);
+    return Ok(());
+}
+
+/// Last segment of a module path, so each day logs under its own name.
+pub fn target(module_path: &str) -> &str {
+    return module_path.rsplit("::").next().unwrap_or(module_path);
+}
+
+pub fn enabled(target: &str, level: Level) -> bool {
+    let filter = FILTER.get_or_init(default_filter);
+    let max = filter
+        .targets
+        .iter()
+        .rev()
+        .find(|(t, _)| t == target)
+        .map_or(filter.default, |&(_, l)| l);
+    return level <= max;
+}
+
+pub fn write(target: &str, level: Level, args: fmt::Arguments) {
+    eprintln!("[{} {target}] {args}", level.name());
+}
+
+/// Logs under the calling module's name if its filter lets `level` through.
+/// The message is only formatted when it is shown.
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {{
+        let target = $crate::logging::target(module_path!());
+        if $crate::logging::enabled(target, $level) {
+            $crate::logging::write(target, $level, format_args!($($arg)+));
+        }
+    }};
+}
+
+macro_rules! warning {
+    ($($arg:tt)+) => {
+        $crate::logging::log!($crate::logging::Level::Warn, $($arg)+)
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)+) => {
+        $crate::logging::log!($crate::logging::Level::Info, $($arg)+)
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        $crate::logging::log!($crate::logging::Level::Debug, $($arg)+)
+    };
+}
+
+pub(crate) use {debug, info, log, warning};
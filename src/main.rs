@@ -1,3 +1,4 @@
+mod budget;
 mod day1;
 mod day10;
 mod day11;
@@ -10,16 +11,78 @@ mod day6;
 mod day7;
 mod day8;
 mod day9;
+mod disjoint_set;
+mod gf2;
+mod graph;
+mod ilp;
+mod logging;
+mod output;
+mod packing;
+mod progress;
+mod rectilinear;
+mod sat;
+mod spatial;
+#[cfg(test)]
+mod test_rng;
 
 use std::fs;
+use std::path::PathBuf;
+use std::process;
 
-fn setup(day: i32) -> String {
+fn setup(day: usize) -> String {
     let filename = format!("data/day{}.txt", day);
     let contents = fs::read_to_string(filename).expect("Could not read file");
     return contents;
 }
 
+/// Command-line options.
+#[derive(Debug, Default)]
+struct Args {
+    /// Hide progress reports
+    quiet: bool,
+    /// `target=level` filters for the logger
+    log: Vec<String>,
+    format: output::Format,
+    /// Directory for the files days can export
+    export_dir: Option<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut ret = Args::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-q" | "--quiet" => ret.quiet = true,
+            "--log" => ret
+                .log
+                .push(args.next().ok_or("Missing filter after `--log`")?),
+            "--format" => {
+                let name = args.next().ok_or("Missing format after `--format`")?;
+                ret.format = output::Format::from_name(&name)
+                    .ok_or_else(|| format!("Unknown format `{name}`"))?;
+            }
+            "--export" => {
+                let dir = args.next().ok_or("Missing directory after `--export`")?;
+                ret.export_dir = Some(PathBuf::from(dir));
+            }
+            _ => return Err(format!("Unknown argument `{arg}`")),
+        }
+    }
+    return Ok(ret);
+}
+
 fn main() {
+    let args = parse_args(std::env::args().skip(1))
+        .and_then(|args| logging::init(&args.log.join(",")).map(|_| args))
+        .unwrap_or_else(|e| {
+            eprintln!(
+                "{e}\nUsage: advent-of-code25 [-q | --quiet] [--format text|csv|json] \
+                 [--export DIR] [--log [target=]level]..."
+            );
+            process::exit(2)
+        });
+    if args.quiet {
+        progress::set_mode(progress::Mode::Quiet);
+    }
     let days: Vec<(&dyn Fn(&String) -> (), bool)> = vec![
         (&day1::day1, false),
         (&day2::day2, false),
@@ -34,13 +97,15 @@ fn main() {
         (&day11::day11, false),
         (&day12::day12, true),
     ];
+    output::begin(args.format, args.export_dir);
     let mut day = 1;
     for (f, show) in &days {
         if *show {
-            println!("\nDay {day} result:\n");
             let contents_j = setup(day);
+            output::start_day(day);
             (f)(&contents_j);
         }
         day += 1;
     }
+    output::finish();
 }
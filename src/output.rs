@@ -0,0 +1,191 @@
+use crate::logging::warning;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How answers are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// `Part N: answer` under a heading per day
+    #[default]
+    Text,
+    /// A header line, then one row per part
+    Csv,
+    /// One array holding an object per part
+    Json,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Text, Format::Csv, Format::Json];
+
+    pub fn name(self) -> &'static str {
+        return match self {
+            Format::Text => "text",
+            Format::Csv => "csv",
+            Format::Json => "json",
+        };
+    }
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        return Format::ALL.into_iter().find(|f| f.name() == name);
+    }
+}
+
+/// Whether a part's answer can be trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// Some items were given up on, so the answer only counts the rest
+    Incomplete,
+    /// No answer, the message says why
+    Error,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        return match self {
+            Status::Ok => "ok",
+            Status::Incomplete => "incomplete",
+            Status::Error => "error",
+        };
+    }
+}
+
+struct Sink {
+    format: Format,
+    day: usize,
+    /// When the day started or its last part was recorded
+    since: Instant,
+    num_records: usize,
+    /// Where days write their extra files, if anywhere
+    export_dir: Option<PathBuf>,
+}
+
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+/// Picks the format and prints whatever comes before the first record. Extra
+/// files only get written if `export_dir` is given.
+pub fn begin(format: Format, export_dir: Option<PathBuf>) {
+    match format {
+        Format::Text => {}
+        Format::Csv => println!("day,part,answer,elapsed_s,status"),
+        Format::Json => println!("["),
+    }
+    *SINK.lock().unwrap() = Some(Sink {
+        format,
+        day: 0,
+        since: Instant::now(),
+        num_records: 0,
+        export_dir,
+    });
+}
+
+/// Starts timing `day`. Parts are timed from here or from the part before.
+pub fn start_day(day: usize) {
+    let mut sink = SINK.lock().unwrap();
+    let sink = sink.as_mut().expect("Output used before `begin`");
+    if sink.format == Format::Text {
+        println!("\nDay {day} result:\n");
+    }
+    sink.day = day;
+    sink.since = Instant::now();
+}
+
+/// Writes `name` into the export directory, if there is one. `contents` is
+/// only built then. A failed write is logged rather than fatal.
+pub fn export(name: &str, contents: impl FnOnce() -> String) {
+    let dir = SINK
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|s| s.export_dir.clone());
+    let Some(dir) = dir else {
+        return;
+    };
+    let path = dir.join(name);
+    if let Err(e) = fs::write(&path, contents()) {
+        warning!("Could not write {}: {e}", path.display());
+    }
+}
+
+pub fn finish() {
+    let sink = SINK.lock().unwrap().take();
+    if sink.is_some_and(|s| s.format == Format::Json) {
+        println!("]");
+    }
+}
+
+pub fn answer(part: u8, answer: impl Display) {
+    record(part, answer, Status::Ok);
+}
+
+/// Records the answer, or the error in its place.
+pub fn report(part: u8, result: Result<impl Display, impl Display>) {
+    match result {
+        Ok(answer) => record(part, answer, Status::Ok),
+        Err(e) => record(part, e, Status::Error),
+    }
+}
+
+pub fn record(part: u8, answer: impl Display, status: Status) {
+    let mut sink = SINK.lock().unwrap();
+    let sink = sink.as_mut().expect("Output used before `begin`");
+    let elapsed = sink.since.elapsed();
+    let answer = answer.to_string();
+    match sink.format {
+        Format::Text => match status {
+            Status::Ok => println!("Part {part}: {answer}"),
+            Status::Incomplete => println!("Part {part}: at least {answer}"),
+            Status::Error => println!("Part {part}: {answer}"),
+        },
+        Format::Csv => println!(
+            "{},{part},{},{},{}",
+            sink.day,
+            csv_field(&answer),
+            seconds(elapsed),
+            status.name()
+        ),
+        Format::Json => {
+            let sep = if sink.num_records == 0 { " " } else { "," };
+            println!(
+                "{sep}{{\"day\":{},\"part\":{part},\"answer\":{},\"elapsed_s\":{},\"status\":\"{}\"}}",
+                sink.day,
+                json_string(&answer),
+                seconds(elapsed),
+                status.name()
+            );
+        }
+    }
+    sink.num_records += 1;
+    sink.since = Instant::now();
+}
+
+fn seconds(elapsed: Duration) -> String {
+    return format!("{:.6}", elapsed.as_secs_f64());
+}
+
+/// Quotes `field` if it holds a comma, quote or line break.
+fn csv_field(field: &str) -> String {
+    if !field.contains([',', '"', '\n', '\r']) {
+        return String::from(field);
+    }
+    return format!("\"{}\"", field.replace('"', "\"\""));
+}
+
+fn json_string(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret += "\\\"",
+            '\\' => ret += "\\\\",
+            '\n' => ret += "\\n",
+            '\t' => ret += "\\t",
+            c if c.is_control() => ret += &format!("\\u{:04x}", c as u32),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    return ret;
+}
@@ -0,0 +1,783 @@
+use crate::budget::{Budget, Meter, Outcome};
+use crate::sat::{self, Lit, Solver};
+use ndarray::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+const WORD: usize = u64::BITS as usize;
+
+/// One way to put a piece in the region: an orientation of a shape with its
+/// bounding box's top-left corner at `(row, col)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub shape: usize,
+    pub orientation: usize,
+    pub row: usize,
+    pub col: usize,
+    /// Covered cells as `row * cols + col`, ascending
+    cells: Vec<usize>,
+}
+
+impl Placement {
+    fn new(
+        shape: usize,
+        orientation: usize,
+        arr: ArrayView2<bool>,
+        (row, col): (usize, usize),
+        cols: usize,
+    ) -> Placement {
+        let cells = arr
+            .indexed_iter()
+            .filter(|(_, &b)| b)
+            .map(|((i, j), _)| (row + i) * cols + col + j)
+            .collect();
+        return Placement {
+            shape,
+            orientation,
+            row,
+            col,
+            cells,
+        };
+    }
+}
+
+/// Packing instance: put exactly `counts[s]` copies of every shape `s` into
+/// a `rows` by `cols` region without overlaps. Cells may stay empty.
+///
+/// Regions wider than they are tall are searched transposed. The engines
+/// fill cells in row-major order, and short rows let them find dead ends
+/// sooner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// Size of the region as searched
+    rows: usize,
+    cols: usize,
+    transposed: bool,
+    counts: Vec<u64>,
+    sizes: Vec<u64>,
+    placements: Vec<Placement>,
+}
+
+impl Problem {
+    /// `shapes[s]` lists the distinct orientations of shape `s`. Counts past
+    /// the end of `shapes` are ignored.
+    pub fn new(rows: usize, cols: usize, shapes: &[Vec<Array2<bool>>], counts: &[u64]) -> Problem {
+        let counts: Vec<u64> = (0..shapes.len())
+            .map(|s| counts.get(s).copied().unwrap_or(0))
+            .collect();
+        let sizes = shapes
+            .iter()
+            .map(|o| {
+                o.first()
+                    .map_or(0, |a| a.iter().filter(|&&b| b).count() as u64)
+            })
+            .collect();
+        let transposed = cols > rows;
+        let (rows, cols) = if transposed {
+            (cols, rows)
+        } else {
+            (rows, cols)
+        };
+        let mut placements = Vec::new();
+        for (shape, orientations) in shapes.iter().enumerate() {
+            if counts[shape] == 0 {
+                continue;
+            }
+            for (orientation, arr) in orientations.iter().enumerate() {
+                let arr = if transposed { arr.t() } else { arr.view() };
+                let max_row = (rows + 1).saturating_sub(arr.nrows());
+                let max_col = (cols + 1).saturating_sub(arr.ncols());
+                for row in 0..max_row {
+                    for col in 0..max_col {
+                        placements.push(Placement::new(shape, orientation, arr, (row, col), cols));
+                    }
+                }
+            }
+        }
+        return Problem {
+            rows,
+            cols,
+            transposed,
+            counts,
+            sizes,
+            placements,
+        };
+    }
+
+    pub fn num_cells(&self) -> usize {
+        return self.rows * self.cols;
+    }
+
+    /// Placement `p` in the region as given, undoing any transpose.
+    fn placement(&self, p: usize) -> Placement {
+        let placement = &self.placements[p];
+        if !self.transposed {
+            return placement.clone();
+        }
+        let mut cells: Vec<usize> = placement
+            .cells
+            .iter()
+            .map(|&c| (c % self.cols) * self.rows + c / self.cols)
+            .collect();
+        cells.sort_unstable();
+        return Placement {
+            row: placement.col,
+            col: placement.row,
+            cells,
+            ..*placement
+        };
+    }
+
+    /// How many cells stay empty in any solution, or `None` if the pieces
+    /// cover more than the region.
+    fn empty_budget(&self) -> Option<u64> {
+        let covered: u64 = self
+            .counts
+            .iter()
+            .zip(&self.sizes)
+            .map(|(c, s)| c * s)
+            .sum();
+        return (self.num_cells() as u64).checked_sub(covered);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Engine {
+    /// Depth-first search over a bitboard, always filling the first
+    /// undecided cell
+    Bitboard,
+    /// Algorithm X with dancing links
+    Dlx,
+    /// CNF encoding handed to the built-in SAT solver
+    Sat,
+}
+
+impl Engine {
+    pub const ALL: [Engine; 3] = [Engine::Bitboard, Engine::Dlx, Engine::Sat];
+
+    pub fn name(self) -> &'static str {
+        return match self {
+            Engine::Bitboard => "bitboard",
+            Engine::Dlx => "dlx",
+            Engine::Sat => "sat",
+        };
+    }
+
+    pub fn from_name(name: &str) -> Option<Engine> {
+        return Engine::ALL.into_iter().find(|e| e.name() == name);
+    }
+}
+
+/// Cheap sound tests that can settle a packing without searching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Filter {
+    /// Every piece gets its own slot the size of the largest bounding box
+    Slots,
+    /// Some required shape fits nowhere in the region
+    Bounds,
+    /// The pieces cannot balance the two colours of a checkerboard
+    Checkerboard,
+    /// The pieces cannot balance the two colours of row or column stripes
+    Stripes,
+}
+
+impl Filter {
+    pub fn name(self) -> &'static str {
+        return match self {
+            Filter::Slots => "slots",
+            Filter::Bounds => "bounds",
+            Filter::Checkerboard => "checkerboard",
+            Filter::Stripes => "stripes",
+        };
+    }
+}
+
+/// Runs the filters from cheapest to dearest. Returns the filter that decided
+/// together with a packing if the pieces fit, or `None` inside if they
+/// cannot. Returns `None` if a search is needed. Assumes the pieces' total
+/// area already fits in the region.
+pub fn prefilter(
+    rows: usize,
+    cols: usize,
+    shapes: &[Vec<Array2<bool>>],
+    counts: &[u64],
+) -> Option<(Option<Vec<Placement>>, Filter)> {
+    let required: Vec<Required> = shapes
+        .iter()
+        .zip(counts.iter().copied())
+        .enumerate()
+        .filter(|&(_, (_, c))| c > 0)
+        .map(|(s, (o, c))| (s, o, c))
+        .collect();
+    if let Some(layout) = slot_layout(rows, cols, &required) {
+        return Some((Some(layout), Filter::Slots));
+    }
+    let fits_somewhere =
+        |o: &Vec<Array2<bool>>| o.iter().any(|a| a.nrows() <= rows && a.ncols() <= cols);
+    if !required.iter().all(|(_, o, _)| fits_somewhere(o)) {
+        return Some((None, Filter::Bounds));
+    }
+    if !colours_balance(rows, cols, &required, |r, c| (r + c) % 2 == 0) {
+        return Some((None, Filter::Checkerboard));
+    }
+    if !colours_balance(rows, cols, &required, |r, _| r % 2 == 0)
+        || !colours_balance(rows, cols, &required, |_, c| c % 2 == 0)
+    {
+        return Some((None, Filter::Stripes));
+    }
+    return None;
+}
+
+/// Shape index, its orientations and how many copies are needed.
+type Required<'a> = (usize, &'a Vec<Array2<bool>>, u64);
+
+/// Puts every piece in its own slot, if there are enough of them. An `a` by
+/// `b` slot holds any orientation with short side at most `a` and long side
+/// at most `b`.
+fn slot_layout(rows: usize, cols: usize, required: &[Required]) -> Option<Vec<Placement>> {
+    let (mut short, mut long) = (1, 1);
+    for &(_, orientations, _) in required {
+        let arr = orientations.first()?;
+        short = short.max(arr.nrows().min(arr.ncols()));
+        long = long.max(arr.nrows().max(arr.ncols()));
+    }
+    let (h, w) = if (rows / short) * (cols / long) >= (rows / long) * (cols / short) {
+        (short, long)
+    } else {
+        (long, short)
+    };
+    let per_row = cols / w;
+    let num_pieces: u64 = required.iter().map(|&(_, _, c)| c).sum();
+    if num_pieces > ((rows / h) * per_row) as u64 {
+        return None;
+    }
+    let mut layout = Vec::new();
+    for &(shape, orientations, count) in required {
+        let orientation = orientations
+            .iter()
+            .position(|a| a.nrows() <= h && a.ncols() <= w)?;
+        for _ in 0..count {
+            let slot = layout.len();
+            let corner = ((slot / per_row) * h, (slot % per_row) * w);
+            layout.push(Placement::new(
+                shape,
+                orientation,
+                orientations[orientation].view(),
+                corner,
+                cols,
+            ));
+        }
+    }
+    return Some(layout);
+}
+
+/// Whether the pieces could cover at most as many cells of each colour as
+/// the region has, for a colouring that repeats every two rows and columns.
+/// Each copy may take any orientation and position parity, so the reachable
+/// totals of one colour are built up copy by copy.
+fn colours_balance(
+    rows: usize,
+    cols: usize,
+    required: &[Required],
+    colour: impl Fn(usize, usize) -> bool,
+) -> bool {
+    let region = (0..rows)
+        .flat_map(|r| (0..cols).map(move |c| (r, c)))
+        .filter(|&(r, c)| colour(r, c))
+        .count();
+    let mut reachable = vec![true];
+    let mut covered = 0;
+    for &(_, orientations, count) in required {
+        let mut options: Vec<usize> = Vec::new();
+        for arr in orientations
+            .iter()
+            .filter(|a| a.nrows() <= rows && a.ncols() <= cols)
+        {
+            for dr in 0..(rows + 1 - arr.nrows()).min(2) {
+                for dc in 0..(cols + 1 - arr.ncols()).min(2) {
+                    let coloured = arr
+                        .indexed_iter()
+                        .filter(|&((i, j), &b)| b && colour(i + dr, j + dc))
+                        .count();
+                    options.push(coloured);
+                }
+            }
+        }
+        options.sort_unstable();
+        options.dedup();
+        let size = orientations[0].iter().filter(|&&b| b).count();
+        for _ in 0..count {
+            let mut next = vec![false; reachable.len() + options.last().copied().unwrap_or(0)];
+            for x in (0..reachable.len()).filter(|&x| reachable[x]) {
+                for &o in &options {
+                    next[x + o] = true;
+                }
+            }
+            reachable = next;
+            covered += size;
+        }
+    }
+    let lowest = covered.saturating_sub(rows * cols - region);
+    return (lowest..reachable.len().min(region + 1)).any(|x| reachable[x]);
+}
+
+/// Looks for a packing of `problem` within `budget`.
+pub fn solve(problem: &Problem, engine: Engine, budget: &Budget) -> Outcome<Vec<Placement>> {
+    let Some(empty_budget) = problem.empty_budget() else {
+        return Outcome::Infeasible;
+    };
+    let meter = budget.start();
+    let chosen = match engine {
+        Engine::Bitboard => Bitboard::new(problem, empty_budget, meter).solve(),
+        Engine::Dlx => Dlx::new(problem, empty_budget, meter).solve(problem.placements.len()),
+        Engine::Sat => solve_sat(problem, meter),
+    };
+    return chosen.map(|c| c.iter().map(|&p| problem.placement(p)).collect());
+}
+
+/// Runs every engine on its own thread and keeps the first definite answer,
+/// cancelling the others. Replaces any cancel flag already in `budget`.
+/// Returns the engine that answered, or the first to give up if none did.
+pub fn race(problem: &Problem, budget: &Budget) -> (Outcome<Vec<Placement>>, Engine) {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let budget = budget.clone().with_cancel(cancel.clone());
+    let (tx, rx) = mpsc::channel();
+    let mut results = thread::scope(|scope| {
+        for engine in Engine::ALL {
+            let (tx, budget) = (tx.clone(), &budget);
+            scope.spawn(move || tx.send((solve(problem, engine, budget), engine)).unwrap());
+        }
+        drop(tx);
+        let mut results = Vec::new();
+        for (outcome, engine) in rx {
+            if outcome.is_known() {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            results.push((outcome, engine));
+        }
+        return results;
+    });
+    let first = results.iter().position(|(o, _)| o.is_known()).unwrap_or(0);
+    return results.swap_remove(first);
+}
+
+/// Checks that `placements` stay inside the region, never overlap and use
+/// every shape exactly as often as `counts` asks.
+pub fn validate(
+    rows: usize,
+    cols: usize,
+    shapes: &[Vec<Array2<bool>>],
+    counts: &[u64],
+    placements: &[Placement],
+) -> Result<(), String> {
+    let mut owner: Array2<Option<usize>> = Array2::from_elem((rows, cols), None);
+    let mut used = vec![0u64; shapes.len()];
+    for (idx, p) in placements.iter().enumerate() {
+        let arr = shapes
+            .get(p.shape)
+            .and_then(|o| o.get(p.orientation))
+            .ok_or_else(|| format!("placement {idx} has no shape {}/{}", p.shape, p.orientation))?;
+        if p.row + arr.nrows() > rows || p.col + arr.ncols() > cols {
+            return Err(format!("placement {idx} leaves the region"));
+        }
+        for ((i, j), _) in arr.indexed_iter().filter(|(_, &b)| b) {
+            let cell = &mut owner[[p.row + i, p.col + j]];
+            if let Some(other) = cell.replace(idx) {
+                let (r, c) = (p.row + i, p.col + j);
+                return Err(format!(
+                    "placements {other} and {idx} overlap at ({r}, {c})"
+                ));
+            }
+        }
+        used[p.shape] += 1;
+    }
+    for (shape, &n) in used.iter().enumerate() {
+        let wanted = counts.get(shape).copied().unwrap_or(0);
+        if n != wanted {
+            return Err(format!("shape {shape} placed {n} times, wanted {wanted}"));
+        }
+    }
+    return Ok(());
+}
+
+/// Draws the region with `.` for empty cells and a letter per present, as in
+/// the puzzle text. Letters repeat after 52 presents.
+pub fn render(
+    rows: usize,
+    cols: usize,
+    shapes: &[Vec<Array2<bool>>],
+    placements: &[Placement],
+) -> String {
+    let letters: Vec<char> = ('A'..='Z').chain('a'..='z').collect();
+    let mut grid: Array2<char> = Array2::from_elem((rows, cols), '.');
+    for (idx, p) in placements.iter().enumerate() {
+        let arr = &shapes[p.shape][p.orientation];
+        for ((i, j), _) in arr.indexed_iter().filter(|(_, &b)| b) {
+            grid[[p.row + i, p.col + j]] = letters[idx % letters.len()];
+        }
+    }
+    let mut ret = String::new();
+    for row in grid.rows() {
+        ret.extend(row.iter());
+        ret.push('\n');
+    }
+    return ret;
+}
+
+/// One variable per placement. No cell is covered by more than one
+/// placement and every shape `s` is placed exactly `counts[s]` times. Cells
+/// left uncovered are the empty ones, so they need no variables of their own.
+fn solve_sat(problem: &Problem, mut meter: Meter) -> Outcome<Vec<usize>> {
+    let mut solver = Solver::new();
+    let vars: Vec<Lit> = problem
+        .placements
+        .iter()
+        .map(|_| Lit::pos(solver.new_var()))
+        .collect();
+    let mut by_cell: Vec<Vec<Lit>> = vec![Vec::new(); problem.num_cells()];
+    let mut by_shape: Vec<Vec<Lit>> = vec![Vec::new(); problem.counts.len()];
+    for (placement, &var) in problem.placements.iter().zip(&vars) {
+        for &cell in &placement.cells {
+            by_cell[cell].push(var);
+        }
+        by_shape[placement.shape].push(var);
+    }
+    for lits in &by_cell {
+        sat::at_most_one(&mut solver, lits);
+    }
+    for (lits, &count) in by_shape.iter().zip(&problem.counts) {
+        sat::exactly(&mut solver, lits, count as usize);
+    }
+    let model = solver.solve(&mut meter);
+    return meter
+        .outcome(model)
+        .map(|model| (0..vars.len()).filter(|&p| model[vars[p].var()]).collect());
+}
+
+/// Search state for `Engine::Bitboard`. A cell's bit is set once it is
+/// covered by a piece or declared empty.
+struct Bitboard<'a> {
+    problem: &'a Problem,
+    /// Placements indexed by the first cell they cover
+    by_anchor: Vec<Vec<usize>>,
+    masks: Vec<Vec<(usize, u64)>>,
+    occupied: Vec<u64>,
+    remaining: Vec<u64>,
+    empty_budget: u64,
+    /// Placements on the board, in the order they were made
+    chosen: Vec<usize>,
+    meter: Meter,
+}
+
+impl<'a> Bitboard<'a> {
+    fn new(problem: &'a Problem, empty_budget: u64, meter: Meter) -> Bitboard<'a> {
+        let mut by_anchor = vec![Vec::new(); problem.num_cells()];
+        let mut masks = Vec::with_capacity(problem.placements.len());
+        for (p_idx, placement) in problem.placements.iter().enumerate() {
+            if let Some(&anchor) = placement.cells.first() {
+                by_anchor[anchor].push(p_idx);
+            }
+            let mut mask: Vec<(usize, u64)> = Vec::new();
+            for &cell in &placement.cells {
+                let (word, bit) = (cell / WORD, 1u64 << (cell % WORD));
+                match mask.last_mut() {
+                    Some((w, m)) if *w == word => *m |= bit,
+                    _ => mask.push((word, bit)),
+                }
+            }
+            masks.push(mask);
+        }
+        return Bitboard {
+            problem,
+            by_anchor,
+            masks,
+            occupied: vec![0; problem.num_cells().div_ceil(WORD)],
+            remaining: problem.counts.clone(),
+            empty_budget,
+            chosen: Vec::new(),
+            meter,
+        };
+    }
+
+    fn solve(mut self) -> Outcome<Vec<usize>> {
+        let found = self.search(0);
+        return self.meter.outcome(found.then_some(self.chosen));
+    }
+
+    fn first_free(&self, from: usize) -> Option<usize> {
+        let num_cells = self.problem.num_cells();
+        let mut word = from / WORD;
+        let mut free = !*self.occupied.get(word)? & (u64::MAX << (from % WORD));
+        loop {
+            if free != 0 {
+                let cell = word * WORD + free.trailing_zeros() as usize;
+                return if cell < num_cells { Some(cell) } else { None };
+            }
+            word += 1;
+            free = !*self.occupied.get(word)?;
+        }
+    }
+
+    fn fits(&self, p_idx: usize) -> bool {
+        return self.masks[p_idx]
+            .iter()
+            .all(|&(word, mask)| self.occupied[word] & mask == 0);
+    }
+
+    fn toggle(&mut self, p_idx: usize) {
+        for &(word, mask) in &self.masks[p_idx] {
+            self.occupied[word] ^= mask;
+        }
+    }
+
+    fn search(&mut self, from: usize) -> bool {
+        if !self.meter.tick() {
+            return false;
+        }
+        if self.remaining.iter().all(|&r| r == 0) {
+            return true;
+        }
+        let Some(cell) = self.first_free(from) else {
+            return false;
+        };
+        for idx in 0..self.by_anchor[cell].len() {
+            let p_idx = self.by_anchor[cell][idx];
+            let shape = self.problem.placements[p_idx].shape;
+            if self.remaining[shape] == 0 || !self.fits(p_idx) {
+                continue;
+            }
+            self.toggle(p_idx);
+            self.remaining[shape] -= 1;
+            self.chosen.push(p_idx);
+            if self.search(cell + 1) {
+                return true;
+            }
+            self.chosen.pop();
+            self.remaining[shape] += 1;
+            self.toggle(p_idx);
+        }
+        if self.empty_budget > 0 {
+            let (word, bit) = (cell / WORD, 1u64 << (cell % WORD));
+            self.occupied[word] |= bit;
+            self.empty_budget -= 1;
+            if self.search(cell + 1) {
+                return true;
+            }
+            self.empty_budget += 1;
+            self.occupied[word] &= !bit;
+        }
+        return false;
+    }
+}
+
+/// Dancing links for exact cover with multiplicities. Every cell is a
+/// primary column that must be covered once, either by a placement or by a
+/// blank row. Each shape, and the blanks, also get a counter column that is
+/// only covered once its count is used up, so only cells are branched on.
+struct Dlx {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    column: Vec<usize>,
+    /// Row each node belongs to. Placements come first, then blank rows.
+    row_id: Vec<usize>,
+    size: Vec<usize>,
+    /// Uses left for each counter column, indexed by header node
+    counters: Vec<Option<u64>>,
+    num_rows: usize,
+    /// A node of every row taken into the solution
+    chosen: Vec<usize>,
+    meter: Meter,
+}
+
+impl Dlx {
+    const ROOT: usize = 0;
+
+    fn new(problem: &Problem, empty_budget: u64, meter: Meter) -> Dlx {
+        let num_cells = problem.num_cells();
+        let num_shapes = problem.counts.len();
+        // Headers: root, then cells, then one counter per shape, then blanks
+        let num_headers = 1 + num_cells + num_shapes + 1;
+        let mut dlx = Dlx {
+            left: (0..num_headers).map(|h| h.wrapping_sub(1)).collect(),
+            right: (0..num_headers).map(|h| h + 1).collect(),
+            up: (0..num_headers).collect(),
+            down: (0..num_headers).collect(),
+            column: (0..num_headers).collect(),
+            row_id: vec![usize::MAX; num_headers],
+            size: vec![0; num_headers],
+            counters: vec![None; num_headers],
+            num_rows: 0,
+            chosen: Vec::new(),
+            meter,
+        };
+        // Only the cell columns sit in the root's ring
+        dlx.left[Dlx::ROOT] = num_cells;
+        dlx.right[num_cells] = Dlx::ROOT;
+        for h in (num_cells + 1)..num_headers {
+            (dlx.left[h], dlx.right[h]) = (h, h);
+        }
+        let shape_header = |s: usize| 1 + num_cells + s;
+        let blank_header = 1 + num_cells + num_shapes;
+        for s in 0..num_shapes {
+            dlx.counters[shape_header(s)] = Some(problem.counts[s]);
+        }
+        dlx.counters[blank_header] = Some(empty_budget);
+        for placement in &problem.placements {
+            let mut headers: Vec<usize> = placement.cells.iter().map(|&c| c + 1).collect();
+            headers.push(shape_header(placement.shape));
+            dlx.add_row(&headers);
+        }
+        for cell in 0..num_cells {
+            dlx.add_row(&[cell + 1, blank_header]);
+        }
+        for h in (num_cells + 1)..num_headers {
+            if dlx.counters[h] == Some(0) {
+                dlx.cover(h);
+            }
+        }
+        return dlx;
+    }
+
+    fn add_row(&mut self, headers: &[usize]) {
+        let first = self.column.len();
+        for (idx, &h) in headers.iter().enumerate() {
+            let node = first + idx;
+            self.left.push(if idx == 0 {
+                first + headers.len() - 1
+            } else {
+                node - 1
+            });
+            self.right.push(if idx + 1 == headers.len() {
+                first
+            } else {
+                node + 1
+            });
+            self.up.push(self.up[h]);
+            self.down.push(h);
+            self.column.push(h);
+            self.row_id.push(self.num_rows);
+            let above = self.up[h];
+            self.down[above] = node;
+            self.up[h] = node;
+            self.size[h] += 1;
+        }
+        self.num_rows += 1;
+    }
+
+    /// Placement indices of a solution, leaving out the blank rows.
+    fn solve(mut self, num_placements: usize) -> Outcome<Vec<usize>> {
+        let found = self.search();
+        let rows = self.chosen.iter().map(|&node| self.row_id[node]);
+        let placements = rows.filter(|&r| r < num_placements).collect();
+        return self.meter.outcome(found.then_some(placements));
+    }
+
+    fn cover(&mut self, c: usize) {
+        let (l, r) = (self.left[c], self.right[c]);
+        self.right[l] = r;
+        self.left[r] = l;
+        let mut i = self.down[c];
+        while i != c {
+            let mut j = self.right[i];
+            while j != i {
+                let (u, d) = (self.up[j], self.down[j]);
+                self.down[u] = d;
+                self.up[d] = u;
+                self.size[self.column[j]] -= 1;
+                j = self.right[j];
+            }
+            i = self.down[i];
+        }
+    }
+
+    fn uncover(&mut self, c: usize) {
+        let mut i = self.up[c];
+        while i != c {
+            let mut j = self.left[i];
+            while j != i {
+                let (u, d) = (self.up[j], self.down[j]);
+                self.down[u] = j;
+                self.up[d] = j;
+                self.size[self.column[j]] += 1;
+                j = self.left[j];
+            }
+            i = self.up[i];
+        }
+        let (l, r) = (self.left[c], self.right[c]);
+        self.right[l] = c;
+        self.left[r] = c;
+    }
+
+    /// Takes `row` into the solution, given its own column is already covered.
+    fn select(&mut self, row: usize) {
+        let mut j = self.right[row];
+        while j != row {
+            let c = self.column[j];
+            match self.counters[c] {
+                Some(count) => {
+                    self.counters[c] = Some(count - 1);
+                    if count == 1 {
+                        self.cover(c);
+                    }
+                }
+                None => self.cover(c),
+            }
+            j = self.right[j];
+        }
+    }
+
+    fn deselect(&mut self, row: usize) {
+        let mut j = self.left[row];
+        while j != row {
+            let c = self.column[j];
+            match self.counters[c] {
+                Some(count) => {
+                    if count == 0 {
+                        self.uncover(c);
+                    }
+                    self.counters[c] = Some(count + 1);
+                }
+                None => self.uncover(c),
+            }
+            j = self.left[j];
+        }
+    }
+
+    fn search(&mut self) -> bool {
+        if !self.meter.tick() {
+            return false;
+        }
+        if self.right[Dlx::ROOT] == Dlx::ROOT {
+            return true;
+        }
+        // Branch on the cell with the fewest remaining options
+        let mut c = self.right[Dlx::ROOT];
+        let mut j = self.right[c];
+        while j != Dlx::ROOT {
+            if self.size[j] < self.size[c] {
+                c = j;
+            }
+            j = self.right[j];
+        }
+        if self.size[c] == 0 {
+            return false;
+        }
+        self.cover(c);
+        let mut row = self.down[c];
+        while row != c {
+            self.select(row);
+            self.chosen.push(row);
+            if self.search() {
+                return true;
+            }
+            self.chosen.pop();
+            self.deselect(row);
+            row = self.down[row];
+        }
+        self.uncover(c);
+        return false;
+    }
+}
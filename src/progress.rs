@@ -0,0 +1,118 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// How long a piped run waits between progress lines.
+const LOG_EVERY: Duration = Duration::from_secs(5);
+
+/// How progress is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// A bar redrawn in place on stderr
+    Bar,
+    /// A line on stderr every `LOG_EVERY`
+    Log,
+    Quiet,
+}
+
+static MODE: OnceLock<Mode> = OnceLock::new();
+
+/// Picks how progress is shown for the rest of the run. Only the first call
+/// has any effect.
+pub fn set_mode(mode: Mode) {
+    let _ = MODE.set(mode);
+}
+
+/// The mode that was set, else a bar on a terminal, log lines when stderr is
+/// piped and nothing under test.
+fn mode() -> Mode {
+    return *MODE.get_or_init(|| {
+        if cfg!(test) {
+            Mode::Quiet
+        } else if std::io::stderr().is_terminal() {
+            Mode::Bar
+        } else {
+            Mode::Log
+        }
+    });
+}
+
+/// Tracks how far one slow loop has got.
+pub struct Progress {
+    label: String,
+    total: u64,
+    done: u64,
+    bar: Option<ProgressBar>,
+    /// When the last line went out, only kept in `Mode::Log`
+    last_log: Option<Instant>,
+}
+
+impl Progress {
+    pub fn new(label: &str, total: u64) -> Progress {
+        let mode = mode();
+        let bar = (mode == Mode::Bar).then(|| {
+            let style =
+                ProgressStyle::with_template("{msg} [{wide_bar}] {pos}/{len} ({eta})").unwrap();
+            return ProgressBar::new(total)
+                .with_style(style)
+                .with_message(String::from(label));
+        });
+        return Progress {
+            label: String::from(label),
+            total,
+            done: 0,
+            bar,
+            last_log: (mode == Mode::Log).then(Instant::now),
+        };
+    }
+
+    pub fn inc(&mut self, n: u64) {
+        self.done += n;
+        if let Some(bar) = &self.bar {
+            bar.inc(n);
+        }
+        if self.last_log.is_some_and(|t| t.elapsed() >= LOG_EVERY) {
+            let percent = 100 * self.done / self.total.max(1);
+            eprintln!("{}: {}/{} ({percent}%)", self.label, self.done, self.total);
+            self.last_log = Some(Instant::now());
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+/// Iterator that counts every item it yields towards a `Progress`.
+pub struct Reported<I> {
+    iter: I,
+    progress: Progress,
+}
+
+impl<I: Iterator> Iterator for Reported<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let item = self.iter.next()?;
+        self.progress.inc(1);
+        return Some(item);
+    }
+}
+
+pub trait ReportProgress: ExactSizeIterator + Sized {
+    /// Reports progress through this iterator under `label`.
+    fn report(self, label: &str) -> Reported<Self> {
+        let progress = Progress::new(label, self.len() as u64);
+        return Reported {
+            iter: self,
+            progress,
+        };
+    }
+}
+
+impl<I: ExactSizeIterator> ReportProgress for I {}
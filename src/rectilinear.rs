@@ -0,0 +1,219 @@
+use itertools::Itertools;
+use ndarray::prelude::*;
+
+pub type Point2 = (u64, u64);
+
+/// Axis-aligned edge. `fixed` is the shared coordinate (x for vertical
+/// edges, y for horizontal ones) and `lo..=hi` the span along the other axis.
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Edge {
+    fixed: u128,
+    lo: u128,
+    hi: u128,
+}
+
+/// Simple polygon whose edges are all horizontal or vertical, with exact
+/// integer containment tests. Coordinates are stored doubled so midpoints
+/// stay integral. Each query is O(n), so it only checks `CompressedGrid` in
+/// tests.
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RectilinearPolygon {
+    vertical: Vec<Edge>,
+    horizontal: Vec<Edge>,
+}
+
+#[cfg(test)]
+impl RectilinearPolygon {
+    /// Builds the closed loop through `vertices`. Returns `None` if two
+    /// consecutive vertices are not axis-aligned.
+    pub fn new(vertices: &[Point2]) -> Option<RectilinearPolygon> {
+        let mut vertical = Vec::new();
+        let mut horizontal = Vec::new();
+        for (idx, &a) in vertices.iter().enumerate() {
+            let b = vertices[(idx + 1) % vertices.len()];
+            let (a, b) = (double(a), double(b));
+            if a.0 == b.0 {
+                vertical.push(Edge {
+                    fixed: a.0,
+                    lo: a.1.min(b.1),
+                    hi: a.1.max(b.1),
+                });
+            } else if a.1 == b.1 {
+                horizontal.push(Edge {
+                    fixed: a.1,
+                    lo: a.0.min(b.0),
+                    hi: a.0.max(b.0),
+                });
+            } else {
+                return None;
+            }
+        }
+        return Some(RectilinearPolygon {
+            vertical,
+            horizontal,
+        });
+    }
+
+    fn on_boundary(&self, p: (u128, u128)) -> bool {
+        let on =
+            |e: &Edge, fixed: u128, free: u128| e.fixed == fixed && e.lo <= free && free <= e.hi;
+        return self.vertical.iter().any(|e| on(e, p.0, p.1))
+            || self.horizontal.iter().any(|e| on(e, p.1, p.0));
+    }
+
+    /// Point-in-polygon on doubled coordinates, boundary inclusive.
+    fn contains_doubled(&self, p: (u128, u128)) -> bool {
+        if self.on_boundary(p) {
+            return true;
+        }
+        // Cast a ray towards +x, counting vertical edges with half-open spans
+        let crossings = self
+            .vertical
+            .iter()
+            .filter(|e| e.fixed > p.0 && e.lo <= p.1 && p.1 < e.hi)
+            .count();
+        return crossings % 2 == 1;
+    }
+
+    /// Whether the rectangle with opposite corners `a` and `b` lies inside
+    /// the polygon, boundary inclusive.
+    pub fn contains_rect(&self, a: Point2, b: Point2) -> bool {
+        let (a, b) = (double(a), double(b));
+        let (x_lo, x_hi) = (a.0.min(b.0), a.0.max(b.0));
+        let (y_lo, y_hi) = (a.1.min(b.1), a.1.max(b.1));
+        if x_lo == x_hi || y_lo == y_hi {
+            return self.contains_segment((x_lo, y_lo), (x_hi, y_hi));
+        }
+        // With no edge cutting through the open rectangle, it is either
+        // entirely inside or entirely outside, so checking its centre suffices
+        let cuts = |e: &Edge, lo: u128, hi: u128, span_lo: u128, span_hi: u128| {
+            lo < e.fixed && e.fixed < hi && e.lo.max(span_lo) < e.hi.min(span_hi)
+        };
+        let is_cut = self
+            .vertical
+            .iter()
+            .any(|e| cuts(e, x_lo, x_hi, y_lo, y_hi))
+            || self
+                .horizontal
+                .iter()
+                .any(|e| cuts(e, y_lo, y_hi, x_lo, x_hi));
+        if is_cut {
+            return false;
+        }
+        return self.contains_doubled(((x_lo + x_hi) / 2, (y_lo + y_hi) / 2));
+    }
+
+    /// Containment of an axis-aligned segment: between consecutive vertex
+    /// coordinates along the segment the answer cannot change, so the
+    /// endpoints and midpoints of those pieces are enough.
+    fn contains_segment(&self, a: (u128, u128), b: (u128, u128)) -> bool {
+        let along_x = a.1 == b.1;
+        let (lo, hi) = if along_x { (a.0, b.0) } else { (a.1, b.1) };
+        let (across, parallel) = if along_x {
+            (&self.vertical, &self.horizontal)
+        } else {
+            (&self.horizontal, &self.vertical)
+        };
+        let mut stops: Vec<u128> = across
+            .iter()
+            .map(|e| e.fixed)
+            .chain(parallel.iter().flat_map(|e| [e.lo, e.hi]))
+            .filter(|&t| lo < t && t < hi)
+            .collect();
+        stops.push(lo);
+        stops.push(hi);
+        stops.sort();
+        stops.dedup();
+        let point = |t: u128| if along_x { (t, a.1) } else { (a.0, t) };
+        let ends_inside = stops.iter().all(|&t| self.contains_doubled(point(t)));
+        let mids_inside = stops
+            .windows(2)
+            .all(|w| self.contains_doubled(point((w[0] + w[1]) / 2)));
+        return ends_inside && mids_inside;
+    }
+}
+
+#[cfg(test)]
+fn double(p: Point2) -> (u128, u128) {
+    return (2 * p.0 as u128, 2 * p.1 as u128);
+}
+
+/// Coordinate-compressed rasterisation of a rectilinear polygon with a 2D
+/// prefix sum of inside cells, answering rectangle containment in O(1).
+///
+/// Each vertex coordinate gets an odd index and each open gap between
+/// consecutive coordinates the even index between them, with a gap of padding
+/// on either side. Inside-ness is constant over every compressed cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedGrid {
+    xs: Vec<u64>,
+    ys: Vec<u64>,
+    prefix: Array2<u64>,
+}
+
+impl CompressedGrid {
+    /// Returns `None` if two consecutive vertices are not axis-aligned.
+    pub fn new(vertices: &[Point2]) -> Option<CompressedGrid> {
+        let xs: Vec<u64> = vertices.iter().map(|v| v.0).sorted().dedup().collect();
+        let ys: Vec<u64> = vertices.iter().map(|v| v.1).sorted().dedup().collect();
+        let shape = (2 * xs.len() + 1, 2 * ys.len() + 1);
+        let mut boundary: Array2<bool> = Array2::default(shape);
+        for (idx, &a) in vertices.iter().enumerate() {
+            let b = vertices[(idx + 1) % vertices.len()];
+            if a.0 != b.0 && a.1 != b.1 {
+                return None;
+            }
+            let (ax, bx) = (compress(&xs, a.0)?, compress(&xs, b.0)?);
+            let (ay, by) = (compress(&ys, a.1)?, compress(&ys, b.1)?);
+            boundary
+                .slice_mut(s![ax.min(bx)..=ax.max(bx), ay.min(by)..=ay.max(by)])
+                .fill(true);
+        }
+        // Flood the outside from the padded corner; everything else is inside
+        let mut outside: Array2<bool> = Array2::default(shape);
+        let mut stack: Vec<(usize, usize)> = vec![(0, 0)];
+        outside[[0, 0]] = true;
+        while let Some((i, j)) = stack.pop() {
+            let neighbors = [
+                (i.wrapping_sub(1), j),
+                (i + 1, j),
+                (i, j.wrapping_sub(1)),
+                (i, j + 1),
+            ];
+            for (ni, nj) in neighbors {
+                if ni < shape.0 && nj < shape.1 && !boundary[[ni, nj]] && !outside[[ni, nj]] {
+                    outside[[ni, nj]] = true;
+                    stack.push((ni, nj));
+                }
+            }
+        }
+        let mut prefix: Array2<u64> = Array2::zeros((shape.0 + 1, shape.1 + 1));
+        for i in 0..shape.0 {
+            for j in 0..shape.1 {
+                prefix[[i + 1, j + 1]] =
+                    !outside[[i, j]] as u64 + prefix[[i, j + 1]] + prefix[[i + 1, j]]
+                        - prefix[[i, j]];
+            }
+        }
+        return Some(CompressedGrid { xs, ys, prefix });
+    }
+
+    /// Whether the rectangle with opposite corners `a` and `b` lies inside
+    /// the polygon, boundary inclusive. Both corners must be vertices of the
+    /// polygon or share their coordinates with some vertex.
+    pub fn contains_rect(&self, a: Point2, b: Point2) -> Option<bool> {
+        let (ax, bx) = (compress(&self.xs, a.0)?, compress(&self.xs, b.0)?);
+        let (ay, by) = (compress(&self.ys, a.1)?, compress(&self.ys, b.1)?);
+        let (x_lo, x_hi) = (ax.min(bx), ax.max(bx) + 1);
+        let (y_lo, y_hi) = (ay.min(by), ay.max(by) + 1);
+        let p = &self.prefix;
+        let inside = p[[x_hi, y_hi]] + p[[x_lo, y_lo]] - p[[x_lo, y_hi]] - p[[x_hi, y_lo]];
+        return Some(inside == ((x_hi - x_lo) * (y_hi - y_lo)) as u64);
+    }
+}
+
+fn compress(coords: &[u64], v: u64) -> Option<usize> {
+    return Some(2 * coords.binary_search(&v).ok()? + 1);
+}
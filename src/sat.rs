@@ -0,0 +1,827 @@
+use crate::budget::Meter;
+use std::ops::Not;
+
+/// A variable or its negation, packed as `2 * var + negated`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub fn pos(var: usize) -> Lit {
+        return Lit((var as u32) << 1);
+    }
+
+    pub fn neg(var: usize) -> Lit {
+        return Lit(((var as u32) << 1) | 1);
+    }
+
+    pub fn var(self) -> usize {
+        return (self.0 >> 1) as usize;
+    }
+
+    fn is_neg(self) -> bool {
+        return self.0 & 1 == 1;
+    }
+
+    fn index(self) -> usize {
+        return self.0 as usize;
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        return Lit(self.0 ^ 1);
+    }
+}
+
+fn lit_value(assigns: &[Option<bool>], lit: Lit) -> Option<bool> {
+    return assigns[lit.var()].map(|v| v != lit.is_neg());
+}
+
+/// Max-heap of unassigned variables ordered by activity.
+#[derive(Debug, Default)]
+struct VarHeap {
+    heap: Vec<usize>,
+    /// Position of each variable in `heap`, if present
+    position: Vec<Option<usize>>,
+}
+
+impl VarHeap {
+    fn contains(&self, var: usize) -> bool {
+        return self.position[var].is_some();
+    }
+
+    fn push(&mut self, var: usize, activity: &[f64]) {
+        if self.contains(var) {
+            return;
+        }
+        self.position[var] = Some(self.heap.len());
+        self.heap.push(var);
+        self.sift_up(self.heap.len() - 1, activity);
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        let top = *self.heap.first()?;
+        let last = self.heap.pop()?;
+        self.position[top] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.position[last] = Some(0);
+            self.sift_down(0, activity);
+        }
+        return Some(top);
+    }
+
+    /// Restores the heap after `var`'s activity went up.
+    fn bumped(&mut self, var: usize, activity: &[f64]) {
+        if let Some(pos) = self.position[var] {
+            self.sift_up(pos, activity);
+        }
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.position[self.heap[a]] = Some(a);
+        self.position[self.heap[b]] = Some(b);
+    }
+
+    fn sift_up(&mut self, mut pos: usize, activity: &[f64]) {
+        while pos > 0 {
+            let parent = (pos - 1) / 2;
+            if activity[self.heap[parent]] >= activity[self.heap[pos]] {
+                break;
+            }
+            self.swap(pos, parent);
+            pos = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut pos: usize, activity: &[f64]) {
+        loop {
+            let mut largest = pos;
+            for child in [2 * pos + 1, 2 * pos + 2] {
+                if child < self.heap.len()
+                    && activity[self.heap[child]] > activity[self.heap[largest]]
+                {
+                    largest = child;
+                }
+            }
+            if largest == pos {
+                return;
+            }
+            self.swap(pos, largest);
+            pos = largest;
+        }
+    }
+}
+
+/// The `idx`-th term (from 0) of the Luby restart sequence 1, 1, 2, 1, 1, 2,
+/// 4, ...
+fn luby(mut idx: u64) -> u64 {
+    let (mut size, mut seq) = (1u64, 0u32);
+    while size < idx + 1 {
+        seq += 1;
+        size = 2 * size + 1;
+    }
+    while size - 1 != idx {
+        size = (size - 1) / 2;
+        seq -= 1;
+        idx %= size;
+    }
+    return 1 << seq;
+}
+
+/// Entry in a literal's watch list. If `blocker`, some other literal of the
+/// clause, is true then the clause is satisfied and need not be looked at.
+#[derive(Debug, Clone, Copy)]
+struct Watcher {
+    clause: usize,
+    blocker: Lit,
+}
+
+/// Conflict-driven clause learning solver with two watched literals, VSIDS
+/// branching, phase saving, Luby restarts and learnt clause deletion by
+/// literal block distance. Clauses are only added before solving.
+#[derive(Debug, Default)]
+pub struct Solver {
+    /// Deleted learnt clauses are left empty
+    clauses: Vec<Vec<Lit>>,
+    /// Learnt clauses still alive, with their literal block distance
+    learnts: Vec<(usize, usize)>,
+    /// Clauses watching each literal, visited when it becomes false
+    watches: Vec<Vec<Watcher>>,
+    assigns: Vec<Option<bool>>,
+    level: Vec<usize>,
+    /// Clause that implied each variable, with the implied literal first
+    reason: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    trail_lim: Vec<usize>,
+    queue_head: usize,
+    activity: Vec<f64>,
+    var_inc: f64,
+    order: VarHeap,
+    polarity: Vec<bool>,
+    seen: Vec<bool>,
+    /// An empty clause was derived while adding clauses
+    unsat: bool,
+    /// How often the search has restarted and cut its learnt clauses
+    restarts: u64,
+    reductions: u64,
+}
+
+impl Solver {
+    pub fn new() -> Solver {
+        return Solver {
+            var_inc: 1.0,
+            ..Default::default()
+        };
+    }
+
+    pub fn new_var(&mut self) -> usize {
+        let var = self.assigns.len();
+        self.watches.push(Vec::new());
+        self.watches.push(Vec::new());
+        self.assigns.push(None);
+        self.level.push(0);
+        self.reason.push(None);
+        self.activity.push(0.0);
+        self.polarity.push(false);
+        self.seen.push(false);
+        self.order.position.push(None);
+        self.order.push(var, &self.activity);
+        return var;
+    }
+
+    /// Adds a clause, dropping literals already false and the whole clause if
+    /// it is already satisfied or a tautology.
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        let mut clause: Vec<Lit> = Vec::with_capacity(lits.len());
+        for &lit in lits {
+            match lit_value(&self.assigns, lit) {
+                Some(true) => return,
+                Some(false) => continue,
+                None => clause.push(lit),
+            }
+        }
+        // A literal and its negation sort next to each other
+        clause.sort_unstable_by_key(|l| l.index());
+        clause.dedup();
+        if clause.windows(2).any(|w| w[0] == !w[1]) {
+            return;
+        }
+        match clause.len() {
+            0 => self.unsat = true,
+            1 => self.enqueue(clause[0], None),
+            _ => {
+                self.attach(clause);
+            }
+        }
+    }
+
+    fn attach(&mut self, clause: Vec<Lit>) -> usize {
+        let c_idx = self.clauses.len();
+        let (a, b) = (clause[0], clause[1]);
+        self.watches[a.index()].push(Watcher {
+            clause: c_idx,
+            blocker: b,
+        });
+        self.watches[b.index()].push(Watcher {
+            clause: c_idx,
+            blocker: a,
+        });
+        self.clauses.push(clause);
+        return c_idx;
+    }
+
+    fn decision_level(&self) -> usize {
+        return self.trail_lim.len();
+    }
+
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.assigns[var] = Some(!lit.is_neg());
+        self.level[var] = self.decision_level();
+        self.reason[var] = reason;
+        self.trail.push(lit);
+    }
+
+    /// Unit propagation. Returns a conflicting clause if one is found.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.queue_head < self.trail.len() {
+            let false_lit = !self.trail[self.queue_head];
+            self.queue_head += 1;
+            let mut watchers = std::mem::take(&mut self.watches[false_lit.index()]);
+            let mut kept = 0;
+            let mut conflict = None;
+            let mut idx = 0;
+            while idx < watchers.len() {
+                let watcher = watchers[idx];
+                idx += 1;
+                if lit_value(&self.assigns, watcher.blocker) == Some(true) {
+                    watchers[kept] = watcher;
+                    kept += 1;
+                    continue;
+                }
+                let clause = &mut self.clauses[watcher.clause];
+                if clause.is_empty() {
+                    continue;
+                }
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                let watcher = Watcher {
+                    clause: watcher.clause,
+                    blocker: first,
+                };
+                if lit_value(&self.assigns, first) == Some(true) {
+                    watchers[kept] = watcher;
+                    kept += 1;
+                    continue;
+                }
+                let replacement =
+                    (2..clause.len()).find(|&k| lit_value(&self.assigns, clause[k]) != Some(false));
+                if let Some(k) = replacement {
+                    clause.swap(1, k);
+                    self.watches[clause[1].index()].push(watcher);
+                    continue;
+                }
+                watchers[kept] = watcher;
+                kept += 1;
+                if lit_value(&self.assigns, first) == Some(false) {
+                    conflict = Some(watcher.clause);
+                    while idx < watchers.len() {
+                        watchers[kept] = watchers[idx];
+                        kept += 1;
+                        idx += 1;
+                    }
+                } else {
+                    self.enqueue(first, Some(watcher.clause));
+                }
+            }
+            watchers.truncate(kept);
+            self.watches[false_lit.index()] = watchers;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        return None;
+    }
+
+    fn bump(&mut self, var: usize) {
+        self.activity[var] += self.var_inc;
+        if self.activity[var] > 1e100 {
+            for a in self.activity.iter_mut() {
+                *a *= 1e-100;
+            }
+            self.var_inc *= 1e-100;
+        }
+        self.order.bumped(var, &self.activity);
+    }
+
+    /// Whether `lit` can be dropped from a learnt clause because following
+    /// its reasons back only reaches literals already in the clause. Marks
+    /// the literals found on the way in `seen` and lists them in `cleared`.
+    fn is_redundant(&mut self, lit: Lit, cleared: &mut Vec<usize>) -> bool {
+        let start = cleared.len();
+        let mut stack = vec![lit];
+        while let Some(lit) = stack.pop() {
+            let Some(reason) = self.reason[lit.var()] else {
+                for &var in &cleared[start..] {
+                    self.seen[var] = false;
+                }
+                cleared.truncate(start);
+                return false;
+            };
+            for k in 1..self.clauses[reason].len() {
+                let l = self.clauses[reason][k];
+                if self.seen[l.var()] || self.level[l.var()] == 0 {
+                    continue;
+                }
+                self.seen[l.var()] = true;
+                cleared.push(l.var());
+                stack.push(l);
+            }
+        }
+        return true;
+    }
+
+    /// First-UIP conflict analysis. Returns the learnt clause, asserting
+    /// literal first, and the level to backjump to.
+    fn analyze(&mut self, mut conflict: usize) -> (Vec<Lit>, usize) {
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut asserting: Option<Lit> = None;
+        let mut trail_idx = self.trail.len();
+        loop {
+            let skip = asserting.is_some() as usize;
+            for k in skip..self.clauses[conflict].len() {
+                let lit = self.clauses[conflict][k];
+                let var = lit.var();
+                if self.seen[var] || self.level[var] == 0 {
+                    continue;
+                }
+                self.seen[var] = true;
+                self.bump(var);
+                if self.level[var] == self.decision_level() {
+                    pending += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+            loop {
+                trail_idx -= 1;
+                if self.seen[self.trail[trail_idx].var()] {
+                    break;
+                }
+            }
+            let lit = self.trail[trail_idx];
+            self.seen[lit.var()] = false;
+            pending -= 1;
+            asserting = Some(lit);
+            if pending == 0 {
+                break;
+            }
+            conflict = self.reason[lit.var()].expect("Implied literal without a reason");
+        }
+        learnt[0] = !asserting.unwrap();
+        let mut minimized = vec![learnt[0]];
+        let mut cleared = Vec::new();
+        for &lit in &learnt[1..] {
+            if !self.is_redundant(lit, &mut cleared) {
+                minimized.push(lit);
+            }
+        }
+        for lit in &learnt[1..] {
+            self.seen[lit.var()] = false;
+        }
+        for var in cleared {
+            self.seen[var] = false;
+        }
+        let mut learnt = minimized;
+        let mut backjump = 0;
+        if learnt.len() > 1 {
+            let max_idx = (1..learnt.len())
+                .max_by_key(|&k| self.level[learnt[k].var()])
+                .unwrap();
+            learnt.swap(1, max_idx);
+            backjump = self.level[learnt[1].var()];
+        }
+        self.var_inc /= 0.95;
+        return (learnt, backjump);
+    }
+
+    /// Number of distinct decision levels among `lits`.
+    fn block_distance(&self, lits: &[Lit]) -> usize {
+        let mut levels: Vec<usize> = lits.iter().map(|l| self.level[l.var()]).collect();
+        levels.sort_unstable();
+        levels.dedup();
+        return levels.len();
+    }
+
+    /// Deletes the worse half of the learnt clauses, keeping glue clauses and
+    /// clauses that are the reason for a current assignment.
+    fn reduce_learnts(&mut self) {
+        self.learnts.sort_by_key(|&(_, lbd)| std::cmp::Reverse(lbd));
+        let half = self.learnts.len() / 2;
+        let mut kept = Vec::with_capacity(self.learnts.len());
+        for (pos, &(c_idx, lbd)) in self.learnts.iter().enumerate() {
+            let first = self.clauses[c_idx][0];
+            let locked = self.reason[first.var()] == Some(c_idx)
+                && lit_value(&self.assigns, first) == Some(true);
+            if pos >= half || lbd <= 2 || locked {
+                kept.push((c_idx, lbd));
+            } else {
+                self.clauses[c_idx] = Vec::new();
+            }
+        }
+        self.learnts = kept;
+    }
+
+    fn cancel_until(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return;
+        }
+        let keep = self.trail_lim[level];
+        for idx in (keep..self.trail.len()).rev() {
+            let var = self.trail[idx].var();
+            self.polarity[var] = self.assigns[var] == Some(true);
+            self.assigns[var] = None;
+            self.reason[var] = None;
+            self.order.push(var, &self.activity);
+        }
+        self.trail.truncate(keep);
+        self.trail_lim.truncate(level);
+        self.queue_head = keep;
+    }
+
+    fn pick_branch(&mut self) -> Option<Lit> {
+        while let Some(var) = self.order.pop(&self.activity) {
+            if self.assigns[var].is_none() {
+                return Some(if self.polarity[var] {
+                    Lit::pos(var)
+                } else {
+                    Lit::neg(var)
+                });
+            }
+        }
+        return None;
+    }
+
+    /// Returns a satisfying assignment, or `None` if the clauses are
+    /// unsatisfiable or `meter` runs out first. Each conflict and each
+    /// decision counts as a node.
+    pub fn solve(&mut self, meter: &mut Meter) -> Option<Vec<bool>> {
+        const RESTART_BASE: u64 = 100;
+        const REDUCE_BASE: u64 = 2000;
+        const REDUCE_STEP: u64 = 300;
+        if self.unsat || self.propagate().is_some() {
+            return None;
+        }
+        let mut conflicts = 0;
+        let mut next_reduce = REDUCE_BASE;
+        loop {
+            if !meter.tick() {
+                return None;
+            }
+            if let Some(conflict) = self.propagate() {
+                if self.decision_level() == 0 {
+                    return None;
+                }
+                conflicts += 1;
+                next_reduce -= 1;
+                let (learnt, backjump) = self.analyze(conflict);
+                let lbd = self.block_distance(&learnt);
+                self.cancel_until(backjump);
+                let asserting = learnt[0];
+                if learnt.len() == 1 {
+                    self.enqueue(asserting, None);
+                } else {
+                    let c_idx = self.attach(learnt);
+                    self.learnts.push((c_idx, lbd));
+                    self.enqueue(asserting, Some(c_idx));
+                }
+                if next_reduce == 0 {
+                    self.reduce_learnts();
+                    self.reductions += 1;
+                    next_reduce = REDUCE_BASE + REDUCE_STEP * self.reductions;
+                }
+                if conflicts >= RESTART_BASE * luby(self.restarts) {
+                    conflicts = 0;
+                    self.restarts += 1;
+                    self.cancel_until(0);
+                }
+                continue;
+            }
+            let Some(lit) = self.pick_branch() else {
+                return Some(self.assigns.iter().map(|v| v == &Some(true)).collect());
+            };
+            self.trail_lim.push(self.trail.len());
+            self.enqueue(lit, None);
+        }
+    }
+}
+
+/// Adds clauses allowing at most one of `lits` to be true, using a ladder of
+/// auxiliary variables for long lists.
+pub fn at_most_one(solver: &mut Solver, lits: &[Lit]) {
+    if lits.len() <= 64 {
+        for (a, &x) in lits.iter().enumerate() {
+            for &y in &lits[a + 1..] {
+                solver.add_clause(&[!x, !y]);
+            }
+        }
+        return;
+    }
+    // `prefix[i]` is true if any of `lits[..=i]` is
+    let prefix: Vec<Lit> = (0..lits.len() - 1)
+        .map(|_| Lit::pos(solver.new_var()))
+        .collect();
+    for (i, &x) in lits.iter().enumerate() {
+        if i + 1 < lits.len() {
+            solver.add_clause(&[!x, prefix[i]]);
+        }
+        if i > 0 {
+            solver.add_clause(&[!x, !prefix[i - 1]]);
+            if i + 1 < lits.len() {
+                solver.add_clause(&[!prefix[i - 1], prefix[i]]);
+            }
+        }
+    }
+}
+
+/// Adds clauses forcing exactly `k` of `lits` to be true, with a sequential
+/// counter where `count[i][j]` means at least `j + 1` of `lits[..=i]` hold.
+pub fn exactly(solver: &mut Solver, lits: &[Lit], k: usize) {
+    if k > lits.len() {
+        solver.add_clause(&[]);
+        return;
+    }
+    if k == 0 {
+        for &x in lits {
+            solver.add_clause(&[!x]);
+        }
+        return;
+    }
+    let mut prev: Vec<Lit> = Vec::new();
+    for (i, &x) in lits.iter().enumerate() {
+        let width = (i + 1).min(k + 1);
+        let count: Vec<Lit> = (0..width).map(|_| Lit::pos(solver.new_var())).collect();
+        for (j, &at_least) in count.iter().enumerate() {
+            let (below, same) = (j.checked_sub(1).map(|b| prev[b]), prev.get(j).copied());
+            // Forward: either the prefix already had enough, or `x` adds one
+            if let Some(same) = same {
+                solver.add_clause(&[!same, at_least]);
+            }
+            match below {
+                Some(below) => solver.add_clause(&[!below, !x, at_least]),
+                None => solver.add_clause(&[!x, at_least]),
+            }
+            // Backward: reaching `j + 1` needs one of those two reasons
+            let mut because_same = vec![!at_least, x];
+            because_same.extend(same);
+            solver.add_clause(&because_same);
+            if let Some(below) = below {
+                let mut because_below = vec![!at_least, below];
+                because_below.extend(same);
+                solver.add_clause(&because_below);
+            }
+        }
+        prev = count;
+    }
+    solver.add_clause(&[prev[k - 1]]);
+    if let Some(&over) = prev.get(k) {
+        solver.add_clause(&[!over]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::Budget;
+    use crate::gf2;
+    use crate::test_rng::Rng;
+
+    fn solve(solver: &mut Solver) -> Option<Vec<bool>> {
+        return solver.solve(&mut Budget::default().start());
+    }
+
+    fn satisfies(model: &[bool], clause: &[Lit]) -> bool {
+        return clause.iter().any(|&l| model[l.var()] != l.is_neg());
+    }
+
+    /// Whether some assignment of `num_vars` variables satisfies every clause.
+    /// Each clause becomes masks of its positive and negative variables.
+    fn brute_force(num_vars: usize, clauses: &[Vec<Lit>]) -> bool {
+        let masks: Vec<(u64, u64)> = clauses
+            .iter()
+            .map(|c| {
+                c.iter().fold((0, 0), |(pos, neg), l| match l.is_neg() {
+                    false => (pos | 1 << l.var(), neg),
+                    true => (pos, neg | 1 << l.var()),
+                })
+            })
+            .collect();
+        return (0..1u64 << num_vars).any(|bits| {
+            masks
+                .iter()
+                .all(|&(pos, neg)| bits & pos != 0 || !bits & neg != 0)
+        });
+    }
+
+    fn random_cnf(rng: &mut Rng, num_vars: usize, num_clauses: usize) -> Vec<Vec<Lit>> {
+        return (0..num_clauses)
+            .map(|_| {
+                (0..1 + rng.below(3))
+                    .map(|_| match rng.below(2) {
+                        0 => Lit::pos(rng.below(num_vars)),
+                        _ => Lit::neg(rng.below(num_vars)),
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+
+    /// Checks the solver's answer on `clauses`, against `expected` if known.
+    fn check(num_vars: usize, clauses: &[Vec<Lit>], expected: bool) -> Solver {
+        let mut solver = Solver::new();
+        for _ in 0..num_vars {
+            solver.new_var();
+        }
+        for clause in clauses {
+            solver.add_clause(clause);
+        }
+        match solve(&mut solver) {
+            Some(model) => {
+                assert!(expected, "{clauses:?}");
+                assert!(clauses.iter().all(|c| satisfies(&model, c)), "{clauses:?}");
+            }
+            None => assert!(!expected, "{clauses:?}"),
+        }
+        return solver;
+    }
+
+    #[test]
+    fn matches_brute_force_on_random_cnf() {
+        let mut rng = Rng::new(44);
+        for _ in 0..500 {
+            let num_vars = 1 + rng.below(10);
+            let num_clauses = rng.below(5 * num_vars);
+            let clauses = random_cnf(&mut rng, num_vars, num_clauses);
+            check(num_vars, &clauses, brute_force(num_vars, &clauses));
+        }
+    }
+
+    /// Random equations saying the XOR of `width` distinct variables is some
+    /// parity, each as the clauses ruling out its wrong parities. Returns the
+    /// clauses, the equations each variable appears in and the parities.
+    fn random_xor(
+        rng: &mut Rng,
+        num_vars: usize,
+        num_eqs: usize,
+        width: usize,
+    ) -> (Vec<Vec<Lit>>, Vec<Vec<usize>>, Vec<bool>) {
+        let mut clauses = Vec::new();
+        let mut columns = vec![Vec::new(); num_vars];
+        let mut parities = Vec::new();
+        for eq in 0..num_eqs {
+            let mut vars = Vec::new();
+            while vars.len() < width {
+                let v = rng.below(num_vars);
+                if !vars.contains(&v) {
+                    vars.push(v);
+                }
+            }
+            let parity = rng.below(2) == 1;
+            for signs in 0..1u32 << width {
+                // A clause is false only under the assignment it negates
+                if (signs.count_ones() % 2 == 1) != parity {
+                    let clause = vars
+                        .iter()
+                        .enumerate()
+                        .map(|(k, &v)| match (signs >> k) & 1 {
+                            1 => Lit::neg(v),
+                            _ => Lit::pos(v),
+                        });
+                    clauses.push(clause.collect());
+                }
+            }
+            for &v in &vars {
+                columns[v].push(eq);
+            }
+            parities.push(parity);
+        }
+        return (clauses, columns, parities);
+    }
+
+    /// Random XOR systems take clause learning thousands of conflicts, enough
+    /// to restart and cut learnt clauses, while Gaussian elimination gives
+    /// the expected answer. Two more equations than variables are usually
+    /// unsatisfiable.
+    #[test]
+    fn matches_elimination_on_random_xor() {
+        let mut rng = Rng::new(3);
+        let num_vars = 50;
+        for num_eqs in [num_vars, num_vars + 2, num_vars, num_vars + 2] {
+            let (clauses, columns, parities) = random_xor(&mut rng, num_vars, num_eqs, 5);
+            let mut meter = Budget::default().start();
+            let expected = gf2::min_weight_solution(&columns, &parities, &mut meter).is_some();
+            let solver = check(num_vars, &clauses, expected);
+            assert!(solver.restarts > 0 && solver.reductions > 0);
+        }
+    }
+
+    /// `pigeons` pigeons in `holes` holes, each hole holding at most one.
+    fn pigeonhole(pigeons: usize, holes: usize) -> Vec<Vec<Lit>> {
+        let var = |p: usize, h: usize| p * holes + h;
+        let mut clauses: Vec<Vec<Lit>> = (0..pigeons)
+            .map(|p| (0..holes).map(|h| Lit::pos(var(p, h))).collect())
+            .collect();
+        for h in 0..holes {
+            for p in 0..pigeons {
+                for q in p + 1..pigeons {
+                    clauses.push(vec![Lit::neg(var(p, h)), Lit::neg(var(q, h))]);
+                }
+            }
+        }
+        return clauses;
+    }
+
+    /// Pigeonhole instances take exponentially many conflicts, so the larger
+    /// ones go through restarts and learnt clause deletion.
+    #[test]
+    fn pigeonhole_needs_restarts_and_reductions() {
+        for holes in 1..=7 {
+            check(holes * holes, &pigeonhole(holes, holes), true);
+            let solver = check((holes + 1) * holes, &pigeonhole(holes + 1, holes), false);
+            if holes == 7 {
+                assert!(solver.restarts > 0 && solver.reductions > 0);
+            }
+        }
+    }
+
+    /// Adds `constrain` over `num_lits` fresh literals, then pins them to
+    /// every pattern in turn, which must be satisfiable exactly when `allowed`.
+    fn check_constraint(
+        num_lits: usize,
+        patterns: impl Iterator<Item = Vec<bool>>,
+        constrain: impl Fn(&mut Solver, &[Lit]),
+        allowed: impl Fn(&[bool]) -> bool,
+    ) {
+        for pattern in patterns {
+            let mut solver = Solver::new();
+            let lits: Vec<Lit> = (0..num_lits).map(|_| Lit::pos(solver.new_var())).collect();
+            constrain(&mut solver, &lits);
+            for (&lit, &value) in lits.iter().zip(&pattern) {
+                solver.add_clause(&[if value { lit } else { !lit }]);
+            }
+            assert_eq!(
+                solve(&mut solver).is_some(),
+                allowed(&pattern),
+                "{pattern:?}"
+            );
+        }
+    }
+
+    fn count(pattern: &[bool]) -> usize {
+        return pattern.iter().filter(|&&b| b).count();
+    }
+
+    #[test]
+    fn exactly_matches_enumeration() {
+        for num_lits in 0..=7 {
+            for k in 0..=num_lits + 1 {
+                let patterns = (0..1u64 << num_lits)
+                    .map(|bits| (0..num_lits).map(|i| (bits >> i) & 1 == 1).collect());
+                check_constraint(
+                    num_lits,
+                    patterns,
+                    |solver, lits| exactly(solver, lits, k),
+                    |pattern| count(pattern) == k,
+                );
+            }
+        }
+    }
+
+    /// Short lists use pairwise clauses and long ones the ladder.
+    #[test]
+    fn at_most_one_on_both_encodings() {
+        let mut rng = Rng::new(1);
+        for num_lits in [1, 2, 5, 64, 65, 100] {
+            let patterns = (0..200).map(|_| {
+                let mut pattern = vec![false; num_lits];
+                for _ in 0..rng.below(3) {
+                    pattern[rng.below(num_lits)] = true;
+                }
+                return pattern;
+            });
+            check_constraint(
+                num_lits,
+                patterns,
+                |solver, lits| at_most_one(solver, lits),
+                |pattern| count(pattern) <= 1,
+            );
+        }
+    }
+}
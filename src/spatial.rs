@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+pub type Point3 = (u32, u32, u32);
+
+/// Squared Euclidean distance. Fits in a `u64` while coordinates differ by
+/// less than 2³¹ on every axis.
+pub fn sq_dist(x: Point3, y: Point3) -> u64 {
+    let d = (
+        x.0.abs_diff(y.0) as u64,
+        x.1.abs_diff(y.1) as u64,
+        x.2.abs_diff(y.2) as u64,
+    );
+    return d.0 * d.0 + d.1 * d.1 + d.2 * d.2;
+}
+
+/// Streams every pair `(i, j, sq_dist)` with `i < j` in increasing order of
+/// squared distance, ties broken by `(i, j)`.
+///
+/// Pairs are produced in shells: the points are bucketed into a grid whose
+/// cell size is the current radius, so every pair within that radius lies in
+/// neighbouring cells. Once a shell is exhausted the radius doubles.
+pub struct ClosestPairs<'a> {
+    coords: &'a [Point3],
+    radius: u64,
+    lower_sq: Option<u64>,
+    max_sq: u64,
+    batch: Vec<(u64, usize, usize)>,
+}
+
+impl<'a> ClosestPairs<'a> {
+    pub fn new(coords: &'a [Point3]) -> ClosestPairs<'a> {
+        let extent = |f: fn(&Point3) -> u32| {
+            let lo = coords.iter().map(f).min().unwrap_or(0);
+            let hi = coords.iter().map(f).max().unwrap_or(0);
+            return (hi - lo) as u64;
+        };
+        let extents = (extent(|c| c.0), extent(|c| c.1), extent(|c| c.2));
+        let max_sq = extents.0 * extents.0 + extents.1 * extents.1 + extents.2 * extents.2;
+        // Start near the typical spacing between points
+        let volume = (extents.0 + 1) as f64 * (extents.1 + 1) as f64 * (extents.2 + 1) as f64;
+        let spacing = (volume / coords.len().max(1) as f64).cbrt();
+        return ClosestPairs {
+            coords,
+            radius: (spacing as u64).max(1),
+            lower_sq: None,
+            max_sq,
+            batch: Vec::new(),
+        };
+    }
+
+    fn cell(&self, c: Point3) -> (u64, u64, u64) {
+        let r = self.radius;
+        return (c.0 as u64 / r, c.1 as u64 / r, c.2 as u64 / r);
+    }
+
+    /// Collects the pairs with `lower_sq < d² <= radius²`, sorted so the
+    /// closest pair is at the end of `batch`.
+    fn fill_batch(&mut self) {
+        let upper_sq = self.radius.saturating_mul(self.radius);
+        let mut grid: HashMap<(u64, u64, u64), Vec<usize>> = HashMap::new();
+        for (idx, &c) in self.coords.iter().enumerate() {
+            grid.entry(self.cell(c)).or_default().push(idx);
+        }
+        for (i, &c_i) in self.coords.iter().enumerate() {
+            let (x, y, z) = self.cell(c_i);
+            for nx in x.saturating_sub(1)..=(x + 1) {
+                for ny in y.saturating_sub(1)..=(y + 1) {
+                    for nz in z.saturating_sub(1)..=(z + 1) {
+                        let Some(bucket) = grid.get(&(nx, ny, nz)) else {
+                            continue;
+                        };
+                        for &j in bucket.iter().filter(|&&j| j > i) {
+                            let d = sq_dist(c_i, self.coords[j]);
+                            if d <= upper_sq && self.lower_sq.is_none_or(|lo| d > lo) {
+                                self.batch.push((d, i, j));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.batch.sort_by(|a, b| b.cmp(a));
+        self.lower_sq = Some(upper_sq);
+        self.radius = self.radius.saturating_mul(2);
+    }
+}
+
+impl Iterator for ClosestPairs<'_> {
+    type Item = (usize, usize, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.batch.is_empty() {
+            if self.lower_sq.is_some_and(|lo| lo >= self.max_sq) {
+                return None;
+            }
+            self.fill_batch();
+        }
+        let (d, i, j) = self.batch.pop()?;
+        return Some((i, j, d));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rng::Rng;
+
+    /// Every pair sorted by `(sq_dist, i, j)`, the reference for
+    /// `ClosestPairs`.
+    fn brute_force(coords: &[Point3]) -> Vec<(u64, usize, usize)> {
+        let mut pairs = Vec::new();
+        for i in 0..coords.len() {
+            for j in i + 1..coords.len() {
+                pairs.push((sq_dist(coords[i], coords[j]), i, j));
+            }
+        }
+        pairs.sort();
+        return pairs;
+    }
+
+    #[test]
+    fn matches_brute_force_on_random_points() {
+        let mut rng = Rng::new(29);
+        for trial in 0..300 {
+            let n = rng.below(40);
+            let span = [2, 10, 1000, 1 << 31][trial % 4];
+            let mut coords: Vec<Point3> = Vec::new();
+            for _ in 0..n {
+                let point = if !coords.is_empty() && rng.below(4) == 0 {
+                    coords[rng.below(coords.len())]
+                } else {
+                    let mut coord = || rng.below(span) as u32;
+                    (coord(), coord(), coord())
+                };
+                coords.push(point);
+            }
+            // Flatten an axis on some trials
+            if trial % 3 == 0 {
+                coords.iter_mut().for_each(|c| c.2 = 7);
+            }
+            let pairs: Vec<_> = ClosestPairs::new(&coords)
+                .map(|(i, j, d)| (d, i, j))
+                .collect();
+            assert_eq!(pairs, brute_force(&coords), "points {coords:?}");
+        }
+    }
+}